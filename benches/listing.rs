@@ -0,0 +1,91 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use focus_timer::{Period, SQLSessionRow, SQLTimerRow, Storage, TimerFilter, TimerPage, TimerStore, StorageError};
+
+const TIMERS: u64 = 100_000;
+
+/// About ten years of history: a timer every hour with one session,
+/// tagged and assigned to one of a few projects.
+fn setup() -> Storage {
+    let storage = Storage::from_memory().unwrap();
+    storage.atomic(|| {
+        for name in ["work", "home", "study"] {
+            storage.insert_project(name)?;
+        }
+        let first = 1_420_070_400;
+        for n in 0..TIMERS {
+            let start = first + n * 3600;
+            let id = storage.insert_timer(&SQLTimerRow {
+                task: format!("task {n}"),
+                start,
+                end: start + 1800,
+                status: 3,
+                project_id: Some(n as i64 % 3 + 1),
+                ..SQLTimerRow::default()
+            })?;
+            storage.insert_session(&SQLSessionRow {
+                timer_id: id,
+                start,
+                end: Some(start + 1800),
+                ..SQLSessionRow::default()
+            })?;
+            storage.add_timer_tag(id, if n % 2 == 0 { "review" } else { "coding" })?;
+        }
+        Ok::<_, StorageError>(())
+    }).unwrap();
+    storage
+}
+
+fn listing(c: &mut Criterion) {
+    let storage = setup();
+    let all = TimerFilter::default();
+    let page = | offset, after_id | TimerPage { limit: 20, offset, after_id };
+
+    c.bench_function("first page", | b | b.iter(|| {
+        storage.get_timers_page(&all, &page(0, None)).unwrap()
+    }));
+    c.bench_function("page 2500 by offset", | b | b.iter(|| {
+        storage.get_timers_page(&all, &page(50_000, None)).unwrap()
+    }));
+    c.bench_function("page 2500 by cursor", | b | b.iter(|| {
+        storage.get_timers_page(&all, &page(0, Some(50_000))).unwrap()
+    }));
+    let month = TimerFilter {
+        date_from: Some(String::from("2020-01-01")),
+        date_to: Some(String::from("2020-02-01")),
+        ..TimerFilter::default()
+    };
+    c.bench_function("month", | b | b.iter(|| {
+        storage.get_timers_by_date(-1, &month).unwrap()
+    }));
+    let project = TimerFilter {
+        project: Some(String::from("work")),
+        tags_include: vec![String::from("review")],
+        ..TimerFilter::default()
+    };
+    c.bench_function("project and tag", | b | b.iter(|| {
+        storage.get_timers_page(&project, &page(0, None)).unwrap()
+    }));
+    c.bench_function("last", | b | b.iter(|| {
+        storage.get_last_timers(20).unwrap()
+    }));
+    c.bench_function("sessions of a timer", | b | b.iter(|| {
+        storage.get_sessions_by_timer(50_000).unwrap()
+    }));
+}
+
+/// Stat and report load the whole history with its sessions and tags.
+fn history(c: &mut Criterion) {
+    let storage = setup();
+    let mut group = c.benchmark_group("history");
+    group.sample_size(10);
+    group.bench_function("stat", | b | b.iter(|| {
+        focus_timer::show_stat(&storage, TimerFilter::default(), false, None).unwrap()
+    }));
+    group.bench_function("report", | b | b.iter(|| {
+        focus_timer::show_report(&storage, TimerFilter::default(), Period::Month).unwrap()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, listing, history);
+criterion_main!(benches);
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+
+pub const BACKUPS_TO_KEEP: usize = 10;
+
+/// Backups of a database are kept in `backups` next to it
+/// and named `<db name>-<YYYYmmdd-HHMMSS-mmm>.db`.
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path.parent().unwrap_or(Path::new(".")).join("backups")
+}
+
+fn db_name(db_path: &Path) -> String {
+    db_path.file_stem()
+        .map(| s | s.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("database"))
+}
+
+/// Path for a new backup, it never points to an existing file.
+pub fn new_backup_path(db_path: &Path) -> PathBuf {
+    let name = format!("{}-{}", db_name(db_path), Utc::now().format("%Y%m%d-%H%M%S-%3f"));
+    let mut path = backup_dir(db_path).join(format!("{name}.db"));
+    let mut n = 1;
+    while path.exists() {
+        path = backup_dir(db_path).join(format!("{name}-{n}.db"));
+        n += 1;
+    }
+    path
+}
+
+/// Backups of the database, the oldest first.
+pub fn list_backups(db_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{}-", db_name(db_path));
+    let mut items: Vec<PathBuf> = match fs::read_dir(backup_dir(db_path)) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(| e | e.path())
+            .filter(| p | p.extension().is_some_and(| e | e == "db"))
+            .filter(| p | p.file_name().is_some_and(| n | n.to_string_lossy().starts_with(&prefix)))
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e)
+    };
+    items.sort();
+    Ok(items)
+}
+
+/// Removes all backups but the latest `keep`, returns the removed ones.
+/// The latest one is always kept, it is usually the one just taken.
+pub fn rotate_backups(db_path: &Path, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let items = list_backups(db_path)?;
+    let n = items.len().saturating_sub(keep.max(1));
+    let removed: Vec<PathBuf> = items.into_iter().take(n).collect();
+    for path in removed.iter() {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Removes the database file with its `-wal` and `-shm` files.
+pub fn remove_database(db_path: &Path) -> io::Result<()> {
+    fs::remove_file(db_path)?;
+    for suffix in ["-wal", "-shm"] {
+        let mut name = db_path.as_os_str().to_owned();
+        name.push(suffix);
+        match fs::remove_file(PathBuf::from(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// A backup given by its path or by its name in the backup directory.
+pub fn find_backup(db_path: &Path, file: &str) -> Option<PathBuf> {
+    [PathBuf::from(file), backup_dir(db_path).join(file)]
+        .into_iter()
+        .find(| p | p.is_file())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_backup_{}", std::process::id()));
+        let db_path = dir.join("database.db");
+        fs::create_dir_all(backup_dir(&db_path)).unwrap();
+        for name in [
+            "database-20250103-100000.db",
+            "database-20250101-100000.db",
+            "database-20250102-100000.db",
+            "other-20250101-100000.db",
+            "notes.txt"
+        ] {
+            fs::write(backup_dir(&db_path).join(name), "").unwrap();
+        }
+        let names = | items: Vec<PathBuf> | items.iter()
+            .map(| p | p.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(list_backups(&db_path).unwrap().len(), 3);
+        assert_eq!(
+            names(rotate_backups(&db_path, 2).unwrap()),
+            vec!["database-20250101-100000.db"]
+        );
+        assert_eq!(
+            names(list_backups(&db_path).unwrap()),
+            vec!["database-20250102-100000.db", "database-20250103-100000.db"]
+        );
+        assert!(find_backup(&db_path, "database-20250103-100000.db").is_some());
+        assert!(find_backup(&db_path, "database-20250101-100000.db").is_none());
+        assert_eq!(
+            names(rotate_backups(&db_path, 0).unwrap()),
+            vec!["database-20250102-100000.db"]
+        );
+        assert_eq!(names(list_backups(&db_path).unwrap()), vec!["database-20250103-100000.db"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_database() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_remove_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("database.db");
+        for name in ["database.db", "database.db-wal", "database.db-shm", "other.db"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        remove_database(&db_path).unwrap();
+        let left: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(| e | e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(left, vec!["other.db"]);
+        fs::write(&db_path, "").unwrap();
+        remove_database(&db_path).unwrap();
+        assert!(remove_database(&db_path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use chrono::{Datelike, Days, NaiveDate};
+
+const BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Width of the terminal from `COLUMNS`, 80 if it is not set.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(| c | c.trim().parse::<usize>().ok())
+        .filter(| c | *c > 0)
+        .unwrap_or(80)
+}
+
+/// Short duration like `2h 05m`, `45m` or `30s`.
+pub fn short_time(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3600 => format!("{}m", s / 60),
+        s => format!("{}h {:02}m", s / 3600, s % 3600 / 60)
+    }
+}
+
+/// Bar of `value` out of `max` at most `width` characters long,
+/// the last character shows eighths of it.
+pub fn bar(value: i64, max: i64, width: usize) -> String {
+    if max <= 0 || value <= 0 {
+        return String::new();
+    }
+    let eighths = (value.min(max) as f64 / max as f64 * (width * 8) as f64).round() as usize;
+    let mut out = BLOCKS[7].to_string().repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        out.push(BLOCKS[eighths % 8 - 1]);
+    }
+    if out.is_empty() {
+        out.push(BLOCKS[0]);
+    }
+    out
+}
+
+/// One character per value, scaled to the largest one. Zeros are spaces.
+pub fn sparkline(values: &[i64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values.iter()
+        .map(| v | match *v {
+            v if v <= 0 || max <= 0 => ' ',
+            v => LEVELS[((v as f64 / max as f64 * 8.0).ceil() as usize).clamp(1, 8) - 1]
+        })
+        .collect()
+}
+
+fn fit(label: &str, width: usize) -> String {
+    if label.chars().count() <= width {
+        return format!("{label:<width$}");
+    }
+    let cut: String = label.chars().take(width.saturating_sub(1)).collect();
+    format!("{:<width$}", format!("{}…", cut.trim_end()))
+}
+
+/// Lines of a horizontal bar chart no wider than `width`:
+/// label, bar and time of each row.
+pub fn bar_chart(rows: &[(String, i64)], width: usize) -> Vec<String> {
+    let max = rows.iter().map(| (_, v) | *v).max().unwrap_or(0);
+    let time_width = rows.iter().map(| (_, v) | short_time(*v).len()).max().unwrap_or(0);
+    let label_width = rows.iter()
+        .map(| (l, _) | l.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(width / 3);
+    let bar_width = width.saturating_sub(label_width + time_width + 2).max(1);
+    rows.iter()
+        .map(| (label, value) | format!(
+            "{} {:<bar_width$} {:>time_width$}",
+            fit(label, label_width),
+            bar(*value, max, bar_width),
+            short_time(*value)
+        ))
+        .collect()
+}
+
+/// Intensity from 0 to 4 of each value: 0 for nothing tracked,
+/// others by the quartile of the non-zero values they are in.
+pub fn levels(values: &[i64]) -> Vec<usize> {
+    let mut tracked: Vec<i64> = values.iter().copied().filter(| v | *v > 0).collect();
+    tracked.sort();
+    let quartiles: Vec<i64> = [25, 50, 75].iter()
+        .filter_map(| p | tracked.get((tracked.len().max(1) - 1) * p / 100).copied())
+        .collect();
+    values.iter()
+        .map(| v | match *v {
+            v if v <= 0 => 0,
+            v => 1 + quartiles.iter().filter(| q | v > **q).count()
+        })
+        .collect()
+}
+
+/// Calendar of `year` with a column per week starting on Monday and a row
+/// per weekday, shaded by `levels` of the seconds tracked on each day.
+pub fn heatmap(year: i32, days: &BTreeMap<NaiveDate, i64>) -> Vec<String> {
+    let (Some(first), Some(next)) = (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    ) else {
+        return vec![];
+    };
+    let start = first - Days::new(first.weekday().num_days_from_monday() as u64);
+    let weeks = ((next - start).num_days() as usize).div_ceil(7);
+    let dates: Vec<NaiveDate> = first.iter_days().take_while(| d | *d < next).collect();
+    let values: Vec<i64> = dates.iter().map(| d | days.get(d).copied().unwrap_or(0)).collect();
+    let mut grid = vec![vec![' '; weeks]; 7];
+    for (date, level) in dates.iter().zip(levels(&values)) {
+        let n = (*date - start).num_days() as usize;
+        grid[n % 7][n / 7] = SHADES[level];
+    }
+
+    let mut months = vec![' '; weeks];
+    for month in 1..=12 {
+        let Some(day) = NaiveDate::from_ymd_opt(year, month, 1) else { continue };
+        let week = (day - start).num_days() as usize / 7;
+        let name: Vec<char> = day.format("%b").to_string().chars().collect();
+        if week + name.len() <= weeks && months[week..week + name.len()].iter().all(| c | *c == ' ') {
+            months[week..week + name.len()].copy_from_slice(&name);
+        }
+    }
+    let mut lines = vec![format!("    {}", months.iter().collect::<String>().trim_end())];
+    for (n, row) in grid.iter().enumerate() {
+        let label = match n {
+            0 => "Mon",
+            2 => "Wed",
+            4 => "Fri",
+            _ => ""
+        };
+        lines.push(format!("{label:<3} {}", row.iter().collect::<String>().trim_end()));
+    }
+    lines.push(format!("    Less {} More", SHADES.iter().collect::<String>()));
+    lines
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(10, 10, 4), "████");
+        assert_eq!(bar(5, 10, 4), "██");
+        assert_eq!(bar(9, 16, 2), "█▏");
+        assert_eq!(bar(1, 1000, 4), "▏");
+        assert_eq!(bar(0, 10, 4), "");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0, 1, 4, 8]), " ▁▄█");
+        assert_eq!(sparkline(&[0, 0]), "  ");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let rows = vec![
+            (String::from("a very long name of a task"), 7200),
+            (String::from("short"), 1800)
+        ];
+        let lines = bar_chart(&rows, 40);
+        assert!(lines.iter().all(| l | l.chars().count() == 40));
+        assert!(lines[0].starts_with("a very long…"));
+        assert!(lines[0].ends_with(" 2h 00m"));
+        assert!(lines[1].ends_with("    30m"));
+        assert_eq!(short_time(45), "45s");
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(levels(&[0, 10, 20, 30, 40, 0]), vec![0, 1, 2, 3, 4, 0]);
+        assert_eq!(levels(&[5, 5, 5]), vec![1, 1, 1]);
+        assert_eq!(levels(&[0, 0]), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let day = | m, d | NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        let days = BTreeMap::from([(day(1, 1), 3600), (day(1, 2), 60), (day(12, 31), 7200)]);
+        let lines = heatmap(2025, &days);
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with("    Jan"));
+        assert!(lines[0].contains("Dec"));
+        // 2025 starts on Wednesday, the first column begins on Monday 2024-12-30
+        assert_eq!(lines[1], format!("Mon  {}", "·".repeat(52)));
+        assert!(lines[3].starts_with("Wed ▒"));
+        assert!(lines[4].starts_with("    ░"));
+        assert!(lines[5].starts_with("Fri ·"));
+        assert_eq!(lines[3].chars().last(), Some('█'));
+        assert!(heatmap(300_000, &days).is_empty());
+    }
+}
@@ -1,71 +1,891 @@
 mod timer;
 mod storage;
+mod pomodoro;
+mod migrations;
+mod store;
+mod memory_store;
+mod backup;
+mod sync;
+mod profile;
+mod timesheet;
+mod chart;
 
 use std::error::Error;
 use std::fmt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Datelike, Utc};
 use csv::Writer;
-pub use timer::{TimerStatus, Timer, TimerCollection, TimerError};
-pub use storage::{Storage, SQLTimerRow, StorageError};
+pub use timer::{TimerStatus, Timer, TimerCollection, TimerError, Session, Pomodoro, Period};
+pub use storage::{
+    Storage,
+    SQLTimerRow,
+    SQLSessionRow,
+    SQLPomodoroRow,
+    SQLProjectRow,
+    SQLEventRow,
+    SQLDeletedRow,
+    TimerSnapshot,
+    TimerFilter,
+    TimerPage,
+    DbProblem,
+    StorageError
+};
+pub use pomodoro::{PomodoroConfig, Phase};
+pub use migrations::{Migration, SCHEMA_VERSION};
+pub use store::TimerStore;
+pub use memory_store::MemoryStore;
+pub use backup::{BACKUPS_TO_KEEP, remove_database};
+pub use sync::SyncReport;
+pub use profile::{Profiles, ProfileError, DEFAULT_PROFILE};
+pub use timesheet::{Timesheet, SheetRows, SheetFormat};
 
 
 #[derive(Debug)]
 pub enum LogicError {
-    ActiveTimerExists
+    ActiveTimerExists,
+    ProjectExists,
+    ProjectIsArchived,
+    WrongDuration,
+    DurationTooLong,
+    WrongWeek
 }
 
 impl fmt::Display for LogicError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LogicError::ActiveTimerExists => write!(f, "Active timer exist") 
+            LogicError::ActiveTimerExists => write!(f, "Active timer exist"),
+            LogicError::ProjectExists => write!(f, "Project with this name exists"),
+            LogicError::ProjectIsArchived => write!(f, "Project is archived"),
+            LogicError::WrongDuration => write!(f, "Wrong duration, use e.g. 30d, 2w or 12h"),
+            LogicError::DurationTooLong => write!(f, "Duration is too long"),
+            LogicError::WrongWeek => write!(f, "Wrong week, use e.g. 2025-W03")
         }
     }
 }
 impl Error for LogicError {}
 
+fn load_timer(storage: &impl TimerStore, id: i64) -> Result<Timer, StorageError> {
+    let mut timer = Timer::try_from(storage.get_timer_by_id(id)?)?;
+    if let Some(project_id) = timer.project_id {
+        timer.project = Some(storage.get_project_by_id(project_id)?.name);
+    }
+    timer.tags = storage.get_tags_by_timer(id)?;
+    Ok(timer.with_sessions(storage.get_sessions_by_timer(id)?))
+}
+
+fn save_timer(storage: &impl TimerStore, timer: &mut Timer) -> Result<(), StorageError> {
+    storage.update_timer(&timer.to_sqlite_row())?;
+    for stored in storage.get_sessions_by_timer(timer.id)? {
+        if !timer.sessions.iter().any(| s | s.id == stored.id) {
+            storage.delete_session(stored.id)?;
+        }
+    }
+    for session in timer.sessions.iter_mut() {
+        if session.id == 0 {
+            session.id = storage.insert_session(&session.to_sqlite_row(timer.id))?;
+        } else {
+            storage.update_session(&session.to_sqlite_row(timer.id))?;
+        }
+    }
+    Ok(())
+}
+
+fn load_collection(
+    storage: &impl TimerStore,
+    rows: Vec<SQLTimerRow>
+) -> Result<TimerCollection, StorageError> {
+    let projects: HashMap<i64, String> = storage.get_projects(true)?
+        .into_iter()
+        .map(| p | (p.id, p.name))
+        .collect();
+    let ids: Vec<i64> = rows.iter().map(| r | r.id).collect();
+    let mut sessions: HashMap<i64, Vec<SQLSessionRow>> = HashMap::new();
+    for session in storage.get_sessions_by_timers(&ids)? {
+        sessions.entry(session.timer_id).or_default().push(session);
+    }
+    let mut pomodoros: HashMap<i64, Vec<SQLPomodoroRow>> = HashMap::new();
+    for pomodoro in storage.get_pomodoros_by_timers(&ids)? {
+        pomodoros.entry(pomodoro.timer_id).or_default().push(pomodoro);
+    }
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    for (timer_id, tag) in storage.get_tags_by_timers(&ids)? {
+        tags.entry(timer_id).or_default().push(tag);
+    }
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id = row.id;
+        let mut timer = Timer::try_from(row)?
+            .with_sessions(sessions.remove(&id).unwrap_or_default())
+            .with_pomodoros(pomodoros.remove(&id).unwrap_or_default());
+        timer.project = timer.project_id.and_then(| id | projects.get(&id).cloned());
+        timer.tags = tags.remove(&id).unwrap_or_default();
+        items.push(timer);
+    }
+    Ok(TimerCollection::from(items))
+}
+
+/// Runs `change` of timer `id` in a transaction and journals
+/// the timer state before and after it so it can be undone.
+fn journaled<T, F>(
+    storage: &impl TimerStore,
+    id: i64,
+    action: &str,
+    change: F
+) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce() -> Result<T, Box<dyn Error>> {
+    storage.atomic(|| {
+        let before = storage.get_snapshot(id)?;
+        let result = change()?;
+        record_event(storage, id, action, before)?;
+        Ok(result)
+    })
+}
+
+fn record_event(
+    storage: &impl TimerStore,
+    id: i64,
+    action: &str,
+    before: Option<TimerSnapshot>
+) -> Result<(), StorageError> {
+    storage.delete_undone_events()?;
+    storage.insert_event(&SQLEventRow {
+        id: 0,
+        timer_id: id,
+        action: action.to_string(),
+        before,
+        after: storage.get_snapshot(id)?,
+        created_at: Utc::now().timestamp() as u64,
+        undone: false,
+        group_id: None
+    })?;
+    Ok(())
+}
+
+/// Runs `change` in a transaction, the events it records
+/// are undone and redone as one.
+fn grouped<T, F>(storage: &impl TimerStore, change: F) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce() -> Result<T, Box<dyn Error>> {
+    storage.atomic(|| {
+        let last = storage.get_next_event(false)?.map_or(0, | e | e.id);
+        let result = change()?;
+        storage.group_events(last)?;
+        Ok(result)
+    })
+}
+
 pub fn new_timer(
-    storage: &Storage,
-    task: String
-) -> Result<i64, StorageError> {
-    let timer = Timer::from(task);
+    storage: &impl TimerStore,
+    task: String,
+    project: Option<String>,
+    tags: Vec<String>
+) -> Result<i64, Box<dyn Error>> {
+    storage.atomic(|| {
+        let id = insert_new_timer(storage, task, project, tags)?;
+        record_event(storage, id, "new", None)?;
+        Ok(id)
+    })
+}
+
+fn insert_new_timer(
+    storage: &impl TimerStore,
+    task: String,
+    project: Option<String>,
+    tags: Vec<String>
+) -> Result<i64, Box<dyn Error>> {
+    let mut timer = Timer::from(task);
+    if let Some(name) = project {
+        let project = storage.get_project_by_name(&name)?;
+        if project.archived {
+            return Err(Box::new(LogicError::ProjectIsArchived));
+        }
+        timer.project_id = Some(project.id);
+    }
     let id = storage.insert_timer(&timer.to_sqlite_row())?;
+    for tag in tags.iter() {
+        storage.add_timer_tag(id, tag)?;
+    }
     Ok(id)
 }
 
-pub fn start_timer(storage: &Storage, id: i64) -> Result<(), Box<dyn Error>> {
-    if storage.count_timers_by_status(TimerStatus::RUN as u32)? > 0 {
-        return Err(Box::new(LogicError::ActiveTimerExists));
+fn parse_time(time_s: String) -> Result<DateTime<Utc>, StorageError> {
+    let t = Storage::str_to_time(time_s)?;
+    DateTime::from_timestamp(t as i64, 0).ok_or(StorageError::WrongDatetimeFormat)
+}
+
+/// Creates a timer for work done in the past, from `date_from` to `date_to`.
+pub fn add_timer(
+    storage: &impl TimerStore,
+    task: String,
+    project: Option<String>,
+    tags: Vec<String>,
+    date_from: String,
+    date_to: String
+) -> Result<i64, Box<dyn Error>> {
+    let (start, end) = (parse_time(date_from)?, parse_time(date_to)?);
+    if end <= start {
+        return Err(Box::new(TimerError::WrongInterval));
+    }
+    storage.atomic(|| {
+        let id = insert_new_timer(storage, task, project, tags)?;
+        let mut timer = load_timer(storage, id)?;
+        timer.add_session(start, end)?;
+        save_timer(storage, &mut timer)?;
+        record_event(storage, id, "add", None)?;
+        Ok(id)
+    })
+}
+
+/// Adds a session in the past to an existing timer.
+pub fn add_session(
+    storage: &impl TimerStore,
+    id: i64,
+    date_from: String,
+    date_to: String
+) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "add session", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.add_session(parse_time(date_from)?, parse_time(date_to)?)?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+/// Corrects task name and time of a timer, `None` keeps the current value.
+pub fn edit_timer(
+    storage: &impl TimerStore,
+    id: i64,
+    task: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    idle: Option<i64>
+) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "edit", || {
+        let mut timer = load_timer(storage, id)?;
+        if timer.status == TimerStatus::DELETED {
+            return Err(Box::new(TimerError::TimerHasFiniteState));
+        }
+        if date_from.is_some() || date_to.is_some() || idle.is_some() {
+            let start = date_from.map(parse_time).transpose()?;
+            let end = date_to.map(parse_time).transpose()?;
+            timer.set_span(start, end, idle)?;
+        }
+        if let Some(task) = task {
+            timer.task = task;
+        }
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+pub fn add_tag(storage: &impl TimerStore, id: i64, tag: String) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "tag", || {
+        storage.get_timer_by_id(id)?;
+        storage.add_timer_tag(id, &tag)?;
+        Ok(())
+    })
+}
+
+pub fn remove_tag(storage: &impl TimerStore, id: i64, tag: String) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "untag", || {
+        storage.get_timer_by_id(id)?;
+        storage.remove_timer_tag(id, &tag)?;
+        Ok(())
+    })
+}
+
+pub fn add_project(storage: &impl TimerStore, name: String) -> Result<i64, Box<dyn Error>> {
+    storage.atomic(|| {
+        if storage.get_project_by_name(&name).is_ok() {
+            return Err(Box::new(LogicError::ProjectExists).into());
+        }
+        Ok(storage.insert_project(&name)?)
+    })
+}
+
+pub fn rename_project(
+    storage: &impl TimerStore,
+    name: String,
+    new_name: String
+) -> Result<(), Box<dyn Error>> {
+    storage.atomic(|| {
+        if storage.get_project_by_name(&new_name).is_ok() {
+            return Err(Box::new(LogicError::ProjectExists).into());
+        }
+        let mut project = storage.get_project_by_name(&name)?;
+        project.name = new_name;
+        storage.update_project(&project)?;
+        Ok(())
+    })
+}
+
+pub fn archive_project(storage: &impl TimerStore, name: String) -> Result<(), Box<dyn Error>> {
+    storage.atomic(|| {
+        let mut project = storage.get_project_by_name(&name)?;
+        project.archived = true;
+        storage.update_project(&project)?;
+        Ok(())
+    })
+}
+
+pub fn show_projects(storage: &impl TimerStore, with_archived: bool) -> Result<(), Box<dyn Error>> {
+    println!("=== Projects ===");
+    let projects = storage.get_projects(with_archived)?;
+    if projects.is_empty() {
+        println!("No projects");
+    }
+    for p in projects {
+        if p.archived {
+            println!("{} (archived)", p.name);
+        } else {
+            println!("{}", p.name);
+        }
     }
-    let mut timer = Timer::from(storage.get_timer_by_id(id)?);
-    timer.set_start()?;
-    storage.update_timer(&timer.to_sqlite_row())?;
     Ok(())
 }
 
-pub fn stop_timer(storage: &Storage, id: i64) -> Result<(), Box<dyn Error>> {
-    let mut timer = Timer::from(storage.get_timer_by_id(id)?);
-    timer.set_stop()?;
-    storage.update_timer(&timer.to_sqlite_row())?;
+pub fn start_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "start", || {
+        if storage.count_timers_by_status(TimerStatus::RUN as u32)? > 0 {
+            return Err(Box::new(LogicError::ActiveTimerExists));
+        }
+        let mut timer = load_timer(storage, id)?;
+        timer.set_start()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+fn switch_to(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    let target = load_timer(storage, id)?;
+    if target.status == TimerStatus::RUN {
+        return Ok(());
+    }
+    for row in storage.get_timers_by_status(TimerStatus::RUN as u32, -1)? {
+        stop_timer(storage, row.id)?;
+    }
+    start_timer(storage, id)
+}
+
+/// Pauses the running timer and starts timer `id` in one transaction.
+pub fn switch_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    grouped(storage, || switch_to(storage, id))
+}
+
+/// Same as `switch_timer` for a timer created for `task`.
+pub fn switch_new_timer(
+    storage: &impl TimerStore,
+    task: String,
+    project: Option<String>,
+    tags: Vec<String>
+) -> Result<i64, Box<dyn Error>> {
+    grouped(storage, || {
+        let id = new_timer(storage, task, project, tags)?;
+        switch_to(storage, id)?;
+        Ok(id)
+    })
+}
+
+pub fn stop_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "stop", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.set_stop()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+pub fn complete_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "complete", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.set_complete()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+pub fn delete_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "delete", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.set_delete()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+pub fn reopen_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "reopen", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.set_reopen()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+pub fn restore_timer(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    journaled(storage, id, "restore", || {
+        let mut timer = load_timer(storage, id)?;
+        timer.set_restore()?;
+        save_timer(storage, &mut timer)?;
+        Ok(())
+    })
+}
+
+/// Parses an age like `30d`, `2w`, `12h` or `45m` into seconds.
+fn parse_age(age: &str) -> Result<i64, LogicError> {
+    let age = age.trim();
+    let unit = match age.chars().last() {
+        Some('w') => 7 * 86400,
+        Some('d') => 86400,
+        Some('h') => 3600,
+        Some('m') => 60,
+        _ => return Err(LogicError::WrongDuration)
+    };
+    match age[..age.len() - 1].parse::<i64>() {
+        Ok(n) if n >= 0 => n.checked_mul(unit).ok_or(LogicError::DurationTooLong),
+        _ => Err(LogicError::WrongDuration)
+    }
+}
+
+/// Time `age` ago, the epoch if it is longer than that.
+fn age_cutoff(age: &str) -> Result<u64, LogicError> {
+    let cutoff = Utc::now().timestamp().saturating_sub(parse_age(age)?).max(0);
+    u64::try_from(cutoff).map_err(|_| LogicError::DurationTooLong)
+}
+
+/// Permanently removes deleted timers, with `older_than` only
+/// the ones deleted earlier than that age. Returns the number removed.
+pub fn purge_timers(
+    storage: &impl TimerStore,
+    older_than: Option<String>
+) -> Result<usize, Box<dyn Error>> {
+    let deleted_before = match older_than {
+        Some(age) => Some(age_cutoff(&age)?),
+        None => None
+    };
+    storage.atomic(|| Ok(storage.purge_timers(deleted_before)?))
+}
+
+pub fn show_trash(storage: &impl TimerStore) -> Result<(), Box<dyn Error>> {
+    println!("=== Deleted tasks ===");
+    let collection = load_collection(
+        storage,
+        storage.get_timers_by_status(TimerStatus::DELETED as u32, -1)?
+    )?;
+    let trash = collection.trash();
+    if trash.is_empty() {
+        println!("Trash is empty");
+    }
+    trash.iter().for_each(| t | t.print());
     Ok(())
 }
 
-pub fn complete_timer(storage: &Storage, id: i64) -> Result<(), Box<dyn Error>> {
-    let mut timer = Timer::from(storage.get_timer_by_id(id)?);
-    timer.set_complete()?;
-    storage.update_timer(&timer.to_sqlite_row())?;
+/// Shows pending schema migrations of the database at `db_path` and
+/// applies them unless `dry_run`. The database is opened without the
+/// automatic upgrade, for a dry run it is only read.
+pub fn migrate(db_path: PathBuf, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let storage = if dry_run {
+        Storage::open_read_only(db_path)?
+    } else {
+        Storage::open(db_path)?
+    };
+    let pending = storage.pending_migrations()?;
+    println!("Database version: {}, app version: {}", storage.version()?, SCHEMA_VERSION);
+    if pending.is_empty() {
+        println!("Database is up to date");
+        return Ok(());
+    }
+    println!("=== Pending migrations ===");
+    for m in pending.iter() {
+        println!("{}: {}", m.version, m.description);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if let Some(backup) = storage.migrate()? {
+        println!("Backup is saved to {}", backup.display());
+    }
+    println!("Database is migrated to version {}", storage.version()?);
     Ok(())
 }
 
-pub fn delete_timer(storage: &Storage, id: i64) -> Result<(), Box<dyn Error>> {
-    let mut timer = Timer::from(storage.get_timer_by_id(id)?);
-    timer.set_delete()?;
-    storage.update_timer(&timer.to_sqlite_row())?;
+fn db_file(storage: &Storage) -> Result<PathBuf, StorageError> {
+    storage.path()
+        .map(Path::to_path_buf)
+        .ok_or(StorageError::FileError(String::from("Database is kept in memory")))
+}
+
+fn copy_db(storage: &Storage) -> Result<PathBuf, Box<dyn Error>> {
+    let target = backup::new_backup_path(&db_file(storage)?);
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    storage.backup_to(&target)?;
+    Ok(target)
+}
+
+/// Copies the database to the backup directory,
+/// only the latest `keep` backups are left.
+pub fn backup(storage: &Storage, keep: usize) -> Result<PathBuf, Box<dyn Error>> {
+    let target = copy_db(storage)?;
+    backup::rotate_backups(&db_file(storage)?, keep)?;
+    Ok(target)
+}
+
+pub fn show_backups(storage: &Storage) -> Result<(), Box<dyn Error>> {
+    let items = backup::list_backups(&db_file(storage)?)?;
+    if items.is_empty() {
+        println!("No backups");
+    }
+    for path in items {
+        println!("{} ({} KB)", path.display(), fs::metadata(&path)?.len() / 1024);
+    }
+    Ok(())
+}
+
+/// Lists profiles, the active one is marked with `*`.
+pub fn show_profiles(profiles: &Profiles) -> Result<(), Box<dyn Error>> {
+    let active = profiles.active();
+    for name in profiles.list()? {
+        let mark = if name == active { "*" } else { " " };
+        println!("{mark} {name}\t{}", profiles.db_path(&name).display());
+    }
+    Ok(())
+}
+
+/// Replaces the database with a backup given by path or name.
+/// The backup has to be a database of this app not newer than it,
+/// the current state is backed up before it is replaced.
+pub fn restore_backup(
+    storage: &mut Storage,
+    file: &str,
+    keep: usize
+) -> Result<PathBuf, Box<dyn Error>> {
+    let db_path = db_file(storage)?;
+    let source = backup::find_backup(&db_path, file)
+        .ok_or(StorageError::FileError(format!("Backup {file} is not found")))?;
+    match Storage::open(source.clone())?.version()? {
+        0 => return Err(StorageError::FileError(
+            format!("{} is not a focus_timer database", source.display())
+        ).into()),
+        v if v > SCHEMA_VERSION => return Err(StorageError::SchemaVersionError.into()),
+        _ => {}
+    }
+    let saved = copy_db(storage)?;
+    storage.restore_from(&source)?;
+    storage.migrate()?;
+    backup::rotate_backups(&db_path, keep)?;
+    Ok(saved)
+}
+
+/// Shows problems of the database and, with `fix`, repairs them.
+/// A backup is taken before anything is changed.
+/// Returns the number of problems left.
+pub fn check_db(storage: &Storage, fix: bool) -> Result<usize, Box<dyn Error>> {
+    let problems = storage.find_problems()?;
+    println!("=== Database check ===");
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(0);
+    }
+    if !fix {
+        for p in problems.iter() {
+            match p.repair() {
+                Some(repair) => println!("{p} (fix: {repair})"),
+                None => println!("{p} (restore a backup)")
+            }
+        }
+        println!("{} problems found, run `db check --fix` to repair them", problems.len());
+        return Ok(problems.len());
+    }
+    if storage.path().is_some() {
+        println!("Backup is saved to {}", copy_db(storage)?.display());
+    }
+    let mut left = 0;
+    for p in problems.iter() {
+        let fixed = match p {
+            DbProblem::ExtraRunning(id) => {
+                stop_timer(storage, *id)?;
+                true
+            },
+            _ => storage.atomic(|| storage.fix_problem(p))?
+        };
+        if fixed {
+            println!("Fixed: {p} ({})", p.repair().unwrap_or_default());
+        } else {
+            left += 1;
+            println!("Not fixed: {p}");
+        }
+    }
+    Ok(left)
+}
+
+/// Merges this database with the one at `other` in both directions,
+/// see `sync::sync`. Both are backed up first.
+pub fn sync_db(storage: &Storage, other: PathBuf) -> Result<SyncReport, Box<dyn Error>> {
+    if !other.is_file() {
+        return Err(StorageError::FileError(format!("{} is not found", other.display())).into());
+    }
+    if other.canonicalize()? == db_file(storage)?.canonicalize()? {
+        return Err(StorageError::FileError(String::from("Cannot sync a database with itself")).into());
+    }
+    let other = Storage::from_path(other)?;
+    copy_db(storage)?;
+    copy_db(&other)?;
+    let report = storage.atomic(|| other.atomic(|| sync::sync(storage, &other)))?;
+    println!("=== Sync ===");
+    println!("Tasks copied: {}", report.copied);
+    println!("Tasks updated: {}", report.updated);
+    println!("Sessions copied or updated: {}", report.sessions);
+    println!("Tasks and sessions deleted: {}", report.deleted);
+    for conflict in report.conflicts.iter() {
+        println!("Conflict: {conflict}");
+    }
+    Ok(report)
+}
+
+/// Age of deleted tasks purged by `db compact` when no retention is set.
+pub const DEFAULT_PURGE_AGE: &str = "30d";
+const RETENTION_DELETED: &str = "retention_deleted";
+const RETENTION_ROLLUP: &str = "retention_rollup";
+const LAST_COMPACT: &str = "last_compact";
+
+/// Replaces completed timers which ended before `before` with one timer
+/// per day and project holding their total time. Time is split by the day
+/// of each session as in the report, so a timer crossing midnight adds to
+/// both days. Tags, pomodoros and single sessions of these timers are dropped.
+/// Returns the number of timers replaced.
+pub fn rollup_timers(storage: &impl TimerStore, before: u64) -> Result<usize, Box<dyn Error>> {
+    storage.atomic(|| {
+        let rows: Vec<SQLTimerRow> = storage.get_timers_by_status(TimerStatus::COMPLETED as u32, -1)?
+            .into_iter()
+            .filter(| row | row.end < before)
+            .collect();
+        let collection = load_collection(storage, rows)?;
+        let mut groups = BTreeMap::new();
+        for (day, tasks) in collection.report(Period::Day, None, None) {
+            for (timer, time_on) in tasks {
+                groups.entry((day, timer.project_id)).or_insert_with(Vec::new).push((timer, time_on));
+            }
+        }
+        // a timer rolled up on one day takes its time on other days along
+        let rolled: BTreeSet<i64> = groups.values()
+            .filter(| tasks | tasks.len() > 1)
+            .flat_map(| tasks | tasks.iter().map(| (t, _) | t.id))
+            .collect();
+        for ((day, project_id), tasks) in groups.iter() {
+            if !tasks.iter().any(| (t, _) | rolled.contains(&t.id)) {
+                continue;
+            }
+            let next = Period::Day.next(*day);
+            let start = tasks.iter()
+                .flat_map(| (t, _) | t.sessions.iter())
+                .filter(| s | s.start < next && s.end.is_none_or(| end | end > *day))
+                .map(| s | s.start.max(*day))
+                .min()
+                .unwrap_or(*day)
+                .timestamp() as u64;
+            let spent = tasks.iter().map(| (_, time_on) | time_on).sum::<i64>().max(0) as u64;
+            let id = storage.insert_timer(&SQLTimerRow {
+                task: format!("Tasks of {}", day.format("%Y-%m-%d")),
+                start,
+                end: start + spent,
+                status: TimerStatus::COMPLETED as u32,
+                project_id: *project_id,
+                completed_at: Some(start + spent),
+                ..SQLTimerRow::default()
+            })?;
+            storage.insert_session(&SQLSessionRow {
+                timer_id: id,
+                start,
+                end: Some(start + spent),
+                ..SQLSessionRow::default()
+            })?;
+        }
+        for id in rolled.iter() {
+            storage.remove_timer(*id)?;
+        }
+        Ok(rolled.len())
+    })
+}
+
+/// Sets what `db compact` does when it runs without options and makes it
+/// run on startup once a day. `off` turns it off, then shows the settings.
+pub fn set_retention(
+    storage: &Storage,
+    deleted: Option<String>,
+    rollup: Option<String>,
+    off: bool
+) -> Result<(), Box<dyn Error>> {
+    if off {
+        storage.set_param_str(RETENTION_DELETED, None)?;
+        storage.set_param_str(RETENTION_ROLLUP, None)?;
+    }
+    for (param, value) in [(RETENTION_DELETED, deleted), (RETENTION_ROLLUP, rollup)] {
+        if let Some(age) = value {
+            parse_age(&age)?;
+            storage.set_param_str(param, Some(age.trim()))?;
+        }
+    }
+    let show = | value: Option<String> | value.unwrap_or_else(|| String::from("never"));
+    println!("=== Retention ===");
+    println!("Deleted tasks are purged after: {}", show(storage.param_str(RETENTION_DELETED)?));
+    println!("Completed tasks are rolled up after: {}", show(storage.param_str(RETENTION_ROLLUP)?));
+    match storage.param_int(LAST_COMPACT)?.and_then(| t | DateTime::from_timestamp(t, 0)) {
+        Some(at) => println!("Last compacted: {}", at.format("%Y-%m-%d %H:%M:%S")),
+        None => println!("Last compacted: never")
+    }
+    Ok(())
+}
+
+/// Purges deleted tasks older than `older_than`, rolls up completed tasks
+/// older than `rollup` (see `rollup_timers`) and shrinks the file.
+/// Ages not given are taken from the retention settings, deleted tasks
+/// default to `DEFAULT_PURGE_AGE`. The database is backed up first.
+pub fn compact_db(
+    storage: &Storage,
+    older_than: Option<String>,
+    rollup: Option<String>
+) -> Result<(), Box<dyn Error>> {
+    let older_than = match older_than {
+        Some(age) => age,
+        None => storage.param_str(RETENTION_DELETED)?
+            .unwrap_or_else(|| DEFAULT_PURGE_AGE.to_string())
+    };
+    let rollup = match rollup {
+        Some(age) => Some(age),
+        None => storage.param_str(RETENTION_ROLLUP)?
+    };
+    let rollup_before = match rollup {
+        Some(age) => Some(age_cutoff(&age)?),
+        None => None
+    };
+    println!("=== Compact ===");
+    if storage.path().is_some() {
+        println!("Backup is saved to {}", backup(storage, BACKUPS_TO_KEEP)?.display());
+    }
+    let size = storage.size()?;
+    println!("Deleted tasks purged: {}", purge_timers(storage, Some(older_than))?);
+    if let Some(before) = rollup_before {
+        println!("Completed tasks rolled up: {}", rollup_timers(storage, before)?);
+    }
+    storage.vacuum()?;
+    storage.set_param_int(LAST_COMPACT, Some(Utc::now().timestamp()))?;
+    println!("Size: {} KB -> {} KB", size / 1024, storage.size()? / 1024);
+    Ok(())
+}
+
+/// Runs `compact_db` if a retention is set and it has not run for a day,
+/// returns whether it ran.
+pub fn auto_compact(storage: &Storage) -> Result<bool, Box<dyn Error>> {
+    if storage.param_str(RETENTION_DELETED)?.is_none() && storage.param_str(RETENTION_ROLLUP)?.is_none() {
+        return Ok(false);
+    }
+    let last = storage.param_int(LAST_COMPACT)?.unwrap_or_default();
+    if Utc::now().timestamp() - last < 86400 {
+        return Ok(false);
+    }
+    compact_db(storage, None, None)?;
+    Ok(true)
+}
+
+/// Undoes or, with `undone`, redoes the next event together with the
+/// rest of its group. Returns the last event of the group.
+fn replay(storage: &impl TimerStore, undone: bool) -> Result<Option<SQLEventRow>, Box<dyn Error>> {
+    storage.atomic(|| {
+        let Some(first) = storage.get_next_event(undone)? else { return Ok(None) };
+        let mut last = first.clone();
+        let in_group = | e: &SQLEventRow | {
+            e.id == first.id || (first.group_id.is_some() && e.group_id == first.group_id)
+        };
+        let mut next = Some(first.clone());
+        while let Some(event) = next.filter(in_group) {
+            let snapshot = if undone { event.after.as_ref() } else { event.before.as_ref() };
+            storage.restore_snapshot(event.timer_id, snapshot)?;
+            storage.set_event_undone(event.id, !undone)?;
+            next = storage.get_next_event(undone)?;
+            if event.id > last.id {
+                last = event;
+            }
+        }
+        Ok(Some(last))
+    })
+}
+
+/// Reverts the latest change, returns it or `None` if there is nothing to undo.
+pub fn undo(storage: &impl TimerStore) -> Result<Option<SQLEventRow>, Box<dyn Error>> {
+    replay(storage, false)
+}
+
+/// Applies again the earliest undone change.
+pub fn redo(storage: &impl TimerStore) -> Result<Option<SQLEventRow>, Box<dyn Error>> {
+    replay(storage, true)
+}
+
+/// Runs pomodoro phases for a timer. The timer is started for each work
+/// interval and paused when it ends, finished intervals are stored as
+/// pomodoros. `wait` is called with every phase and should block for its
+/// duration.
+pub fn run_pomodoro<F>(
+    storage: &impl TimerStore,
+    id: i64,
+    config: &PomodoroConfig,
+    mut wait: F
+) -> Result<u32, Box<dyn Error>>
+where
+    F: FnMut(&Phase) {
+    let mut done = 0;
+    for phase in config.phases() {
+        println!("=== {phase} ===");
+        match phase {
+            Phase::Work(_) => {
+                start_timer(storage, id)?;
+                let start = Utc::now().timestamp() as u64;
+                wait(&phase);
+                // the task may be stopped, completed or switched by another process meanwhile
+                let recorded = storage.atomic(|| -> Result<bool, Box<dyn Error>> {
+                    if load_timer(storage, id)?.status != TimerStatus::RUN {
+                        return Ok(false);
+                    }
+                    journaled(storage, id, "pomodoro", || {
+                        let mut timer = load_timer(storage, id)?;
+                        timer.set_stop()?;
+                        save_timer(storage, &mut timer)?;
+                        storage.insert_pomodoro(&SQLPomodoroRow {
+                            id: 0,
+                            timer_id: id,
+                            start,
+                            end: Utc::now().timestamp() as u64
+                        })?;
+                        Ok(true)
+                    })
+                })?;
+                if !recorded {
+                    println!("Task is not running anymore, pomodoro is stopped");
+                    break;
+                }
+                done += 1;
+                println!("Pomodoro {done} of {} is done", config.cycles);
+            },
+            _ => wait(&phase)
+        }
+    }
+    Ok(done)
+}
+
+pub fn show_sessions(storage: &impl TimerStore, id: i64) -> Result<(), Box<dyn Error>> {
+    load_timer(storage, id)?.print_sessions();
     Ok(())
 }
 
-pub fn current_info(storage: &Storage) -> Result<(), Box<dyn Error>> {
-    let collection = TimerCollection::from(
+pub fn current_info(storage: &impl TimerStore) -> Result<(), Box<dyn Error>> {
+    let collection = load_collection(
+        storage,
         storage.get_timers_by_status(TimerStatus::RUN as u32, -1)?
-    );
+    )?;
     println!("=== Active task ===");
     if collection.size() == 0 {
         println!("No active task");
@@ -75,9 +895,9 @@ pub fn current_info(storage: &Storage) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub fn show_last_n(storage: &Storage, n: u64) -> Result<(), Box<dyn Error>> {
+pub fn show_last_n(storage: &impl TimerStore, n: u64) -> Result<(), Box<dyn Error>> {
     println!("=== Last 10 changed tasks ===");
-    let collection = TimerCollection::from(storage.get_last_timers(n)?);
+    let collection = load_collection(storage, storage.get_last_timers(n)?)?;
     if collection.size() == 0 {
         println!("No last active tasks")
     } else {
@@ -86,40 +906,126 @@ pub fn show_last_n(storage: &Storage, n: u64) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Shows timers whose task matches `query`, best matches first.
+pub fn search(storage: &impl TimerStore, query: &str, limit: i32) -> Result<(), Box<dyn Error>> {
+    let collection = load_collection(storage, storage.search_timers(query, limit)?)?;
+    println!("=== Search: {query} ===");
+    if collection.size() == 0 {
+        println!("Nothing is found");
+    }
+    collection.print_items();
+    Ok(())
+}
+
+/// Prints a page of timers, if it is full the cursor
+/// for the next one is printed after it.
 pub fn show_list(
-    storage: &Storage,
-    limit: i32,
-    date_from: Option<String>,
-    date_to: Option<String>
+    storage: &impl TimerStore,
+    page: TimerPage,
+    filter: TimerFilter
 ) -> Result<(), Box<dyn Error>> {
-    let collection = TimerCollection::from(
-        storage.get_timers_by_date(limit, date_from, date_to)?
-    );
+    let rows = storage.get_timers_page(&filter, &page)?;
+    let next = match rows.last() {
+        Some(last) if page.limit > 0 && rows.len() == page.limit as usize => Some(last.id),
+        _ => None
+    };
+    let collection = load_collection(storage, rows)?;
     collection.print_items();
+    if let Some(id) = next {
+        println!("Next page: --after-id {id}");
+    }
     Ok(())
 }
 
+/// Prints the stat, with `chart_days` followed by charts
+/// of the tasks and of that many last days.
 pub fn show_stat(
-    storage: &Storage,
-    date_from: Option<String>,
-    date_to: Option<String>
+    storage: &impl TimerStore,
+    filter: TimerFilter,
+    by_tag: bool,
+    chart_days: Option<usize>
+) -> Result<(), Box<dyn Error>> {
+    let collection = load_collection(
+        storage,
+        storage.get_timers_by_date(-1, &filter)?
+    )?;
+    collection.print_stat(by_tag);
+    if let Some(days) = chart_days {
+        collection.print_chart(days, chart::terminal_width());
+    }
+    Ok(())
+}
+
+/// Prints time per task for each period of the filter's date range.
+/// Tasks started before the range are included if they have
+/// sessions in it, only the time inside the range is counted.
+pub fn show_report(
+    storage: &impl TimerStore,
+    filter: TimerFilter,
+    period: Period
+) -> Result<(), Box<dyn Error>> {
+    let time = | s: &Option<String> | -> Result<Option<DateTime<Utc>>, StorageError> {
+        match s {
+            Some(s) => Ok(DateTime::from_timestamp(Storage::str_to_time(s.clone())? as i64, 0)),
+            None => Ok(None)
+        }
+    };
+    let (from, to) = (time(&filter.date_from)?, time(&filter.date_to)?);
+    let rows = storage.get_timers_by_date(-1, &TimerFilter { date_from: None, ..filter })?;
+    load_collection(storage, rows)?.print_report(period, from, to);
+    Ok(())
+}
+
+/// Prints time per task or project and weekday of an ISO week
+/// (`2025-W03`), the current one if it is not given.
+pub fn show_timesheet(
+    storage: &impl TimerStore,
+    filter: TimerFilter,
+    week: Option<String>,
+    by: SheetRows,
+    format: SheetFormat
+) -> Result<(), Box<dyn Error>> {
+    let start = match week {
+        Some(week) => timesheet::parse_week(&week).ok_or(LogicError::WrongWeek)?,
+        None => Period::Week.start_of(Utc::now())
+    };
+    let end = Period::Week.next(start).format("%Y-%m-%d").to_string();
+    let rows = storage.get_timers_by_date(-1, &TimerFilter {
+        date_from: None,
+        date_to: Some(end),
+        ..filter
+    })?;
+    let collection = load_collection(storage, rows)?;
+    print!("{}", Timesheet::new(&collection, start, by).render(format)?);
+    Ok(())
+}
+
+/// Prints a calendar of `year` (the current one if it is not given)
+/// shaded by time tracked per day.
+pub fn show_heatmap(
+    storage: &impl TimerStore,
+    filter: TimerFilter,
+    year: Option<i32>
 ) -> Result<(), Box<dyn Error>> {
-    let collection = TimerCollection::from(
-        storage.get_timers_by_date(-1, date_from, date_to)?
-    );
-    collection.print_stat();
+    let year = year.unwrap_or_else(|| Utc::now().year());
+    let rows = storage.get_timers_by_date(-1, &TimerFilter {
+        date_from: None,
+        date_to: Some(format!("{}-01-01", year + 1)),
+        ..filter
+    })?;
+    load_collection(storage, rows)?.print_heatmap(year);
     Ok(())
 }
 
 pub fn export(
-    storage: &Storage,
+    storage: &impl TimerStore,
     path_str: String,
-    date_from: Option<String>,
-    date_to: Option<String>
+    filter: TimerFilter
 ) -> Result<(), Box<dyn Error>> {
-    let collection = TimerCollection::from(
-        storage.get_timers_by_date(-1, date_from, date_to)?
-    );
+    let collection = load_collection(
+        storage,
+        storage.get_timers_by_date(-1, &filter)?
+    )?;
     let mut wrt = Writer::from_path(path_str)?;
     for item in collection.items().iter() {
         wrt.serialize(item)?
@@ -135,9 +1041,650 @@ mod tests {
     #[test]
     fn test_flow() {
         let storage = Storage::from_memory().expect("err");
-        let id = new_timer(&storage, String::from("test")).expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
         assert_eq!(id, 1);
-        let timer = Timer::from(storage.get_timer_by_id(id).expect("err"));
+        let timer = Timer::try_from(storage.get_timer_by_id(id).expect("err")).expect("err");
+        assert_eq!(timer.status, TimerStatus::NEW);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let storage = MemoryStore::new();
+        add_project(&storage, String::from("work")).expect("err");
+        let first = new_timer(&storage, String::from("first"), Some(String::from("work")), vec![]).expect("err");
+        let second = new_timer(&storage, String::from("second"), None, vec![String::from("review")]).expect("err");
+        start_timer(&storage, first).expect("err");
+        assert!(start_timer(&storage, second).is_err());
+        switch_timer(&storage, second).expect("err");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, second).expect("err").tags, vec![String::from("review")]);
+
+        assert_eq!(undo(&storage).expect("err").unwrap().action, "start");
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::NEW);
+        delete_timer(&storage, second).expect("err");
+        assert_eq!(purge_timers(&storage, None).expect("err"), 1);
+
+        let rows = storage.get_timers_by_date(
+            -1,
+            &TimerFilter { project: Some(String::from("work")), ..TimerFilter::default() }
+        ).expect("err");
+        assert_eq!(load_collection(&storage, rows).expect("err").size(), 1);
+    }
+
+    #[test]
+    fn test_backup_restore() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_restore_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("err");
+        let mut storage = Storage::from_path(dir.join("database.db")).expect("err");
+        assert!(backup(&Storage::from_memory().expect("err"), 1).is_err());
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        let copy = backup(&storage, 1).expect("err");
+        delete_timer(&storage, id).expect("err");
+
+        fs::write(dir.join("junk.db"), "not a database").expect("err");
+        assert!(restore_backup(&mut storage, dir.join("junk.db").to_str().unwrap(), 5).is_err());
+        assert!(restore_backup(&mut storage, "missing.db", 5).is_err());
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::DELETED);
+
+        let name = copy.file_name().unwrap().to_str().unwrap();
+        let saved = restore_backup(&mut storage, name, 5).expect("err");
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::NEW);
+        assert!(saved.is_file());
+        fs::remove_dir_all(&dir).expect("err");
+    }
+
+    #[test]
+    fn test_concurrent_start() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_threads_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("err");
+        let path = dir.join("database.db");
+        let storage = Storage::from_path(path.clone()).expect("err");
+        let ids: Vec<i64> = (0..8)
+            .map(| n | new_timer(&storage, format!("task {n}"), None, vec![]).expect("err"))
+            .collect();
+
+        for _ in 0..5 {
+            let reader = {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let storage = Storage::from_path(path).expect("err");
+                    for _ in 0..50 {
+                        let rows = storage.get_timers_by_status(TimerStatus::RUN as u32, -1).expect("err");
+                        assert!(rows.len() <= 1);
+                        load_collection(&storage, rows).expect("err");
+                    }
+                })
+            };
+            let writers: Vec<_> = ids.iter().map(| &id | {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let storage = Storage::from_path(path).expect("err");
+                    match start_timer(&storage, id) {
+                        Ok(()) => true,
+                        Err(e) => match e.downcast_ref::<LogicError>() {
+                            Some(LogicError::ActiveTimerExists) => false,
+                            _ => panic!("{e}")
+                        }
+                    }
+                })
+            }).collect();
+            let started = writers.into_iter().map(| h | h.join().unwrap()).filter(| ok | *ok).count();
+            reader.join().unwrap();
+
+            assert_eq!(started, 1);
+            let running = storage.get_timers_by_status(TimerStatus::RUN as u32, -1).expect("err");
+            assert_eq!(running.len(), 1);
+            stop_timer(&storage, running[0].id).expect("err");
+        }
+        fs::remove_dir_all(&dir).expect("err");
+    }
+
+    #[test]
+    fn test_check_db() {
+        let storage = Storage::from_memory().expect("err");
+        let first = new_timer(&storage, String::from("first"), None, vec![]).expect("err");
+        let second = new_timer(&storage, String::from("second"), None, vec![]).expect("err");
+        start_timer(&storage, first).expect("err");
+        let mut row = storage.get_timer_by_id(second).expect("err");
+        row.status = TimerStatus::RUN as u32;
+        row.start += 10;
+        row.end = row.start;
+        storage.update_timer(&row).expect("err");
+        storage.insert_session(&SQLSessionRow {
+            timer_id: second,
+            start: row.start,
+            ..SQLSessionRow::default()
+        })
+            .expect("err");
+
+        assert_eq!(check_db(&storage, false).expect("err"), 1);
+        assert_eq!(check_db(&storage, true).expect("err"), 0);
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::RUN);
+        assert_eq!(check_db(&storage, false).expect("err"), 0);
+    }
+
+    #[test]
+    fn test_unknown_status() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("broken"), None, vec![]).expect("err");
+        let mut row = storage.get_timer_by_id(id).expect("err");
+        row.status = 7;
+        storage.update_timer(&row).expect("err");
+
+        assert_eq!(
+            load_timer(&storage, id).unwrap_err().to_string(),
+            StorageError::UnknownStatus(id, 7).to_string()
+        );
+        assert!(show_list(&storage, TimerPage::default(), TimerFilter::default()).is_err());
+        assert!(show_stat(&storage, TimerFilter::default(), false, None).is_err());
+        assert_eq!(check_db(&storage, false).expect("err"), 1);
+        assert_eq!(check_db(&storage, true).expect("err"), 0);
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::PAUSED);
+        assert!(show_list(&storage, TimerPage::default(), TimerFilter::default()).is_ok());
+    }
+
+    #[test]
+    fn test_sessions_saved() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        start_timer(&storage, id).expect("err");
+        stop_timer(&storage, id).expect("err");
+        start_timer(&storage, id).expect("err");
+        complete_timer(&storage, id).expect("err");
+
+        let sessions = storage.get_sessions_by_timer(id).expect("err");
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(| s | s.end.is_some()));
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.status, TimerStatus::COMPLETED);
+        assert_eq!(timer.sessions.len(), 2);
+    }
+
+    #[test]
+    fn test_projects() {
+        let storage = Storage::from_memory().expect("err");
+        add_project(&storage, String::from("work")).expect("err");
+        assert!(add_project(&storage, String::from("work")).is_err());
+        let id = new_timer(&storage, String::from("test"), Some(String::from("work")), vec![]).expect("err");
+        assert!(new_timer(&storage, String::from("test"), Some(String::from("home")), vec![]).is_err());
+        assert_eq!(load_timer(&storage, id).expect("err").project, Some(String::from("work")));
+
+        rename_project(&storage, String::from("work"), String::from("job")).expect("err");
+        archive_project(&storage, String::from("job")).expect("err");
+        assert!(new_timer(&storage, String::from("test"), Some(String::from("job")), vec![]).is_err());
+        let collection = load_collection(
+            &storage,
+            storage.get_timers_by_date(
+                -1,
+                &TimerFilter { project: Some(String::from("job")), ..TimerFilter::default() }
+            ).expect("err")
+        ).expect("err");
+        assert_eq!(collection.size(), 1);
+        assert_eq!(collection.items()[0].project, Some(String::from("job")));
+    }
+
+    #[test]
+    fn test_tags() {
+        let storage = Storage::from_memory().expect("err");
+        let tags = vec![String::from("coding"), String::from("review")];
+        let id = new_timer(&storage, String::from("test"), None, tags).expect("err");
+        add_tag(&storage, id, String::from("meeting")).expect("err");
+        remove_tag(&storage, id, String::from("coding")).expect("err");
+        assert!(add_tag(&storage, 100, String::from("coding")).is_err());
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.tags, vec![String::from("meeting"), String::from("review")]);
+    }
+
+    #[test]
+    fn test_add_timer() {
+        let storage = Storage::from_memory().expect("err");
+        let id = add_timer(
+            &storage,
+            String::from("call"),
+            None,
+            vec![],
+            String::from("2025-01-10 14:00"),
+            String::from("2025-01-10 14:45")
+        ).expect("err");
+        add_session(
+            &storage,
+            id,
+            String::from("2025-01-11 10:00"),
+            String::from("2025-01-11 10:15")
+        ).expect("err");
+        assert!(add_session(
+            &storage,
+            id,
+            String::from("2025-01-10 14:30"),
+            String::from("2025-01-10 15:00")
+        ).is_err());
+        assert!(add_timer(
+            &storage,
+            String::from("call"),
+            None,
+            vec![],
+            String::from("2025-01-10 14:45"),
+            String::from("2025-01-10 14:00")
+        ).is_err());
+
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.status, TimerStatus::PAUSED);
+        assert_eq!(timer.time_on(), 3600);
+        assert_eq!(timer.start, parse_time(String::from("2025-01-10 14:00")).unwrap());
+        let rows = storage.get_timers_by_date(-1, &TimerFilter {
+            date_from: Some(String::from("2025-01-10")),
+            date_to: Some(String::from("2025-01-11")),
+            ..TimerFilter::default()
+        }).expect("err");
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn test_edit_timer() {
+        let storage = Storage::from_memory().expect("err");
+        let id = add_timer(
+            &storage,
+            String::from("call"),
+            None,
+            vec![],
+            String::from("2025-01-10 09:00"),
+            String::from("2025-01-10 12:00")
+        ).expect("err");
+        add_session(
+            &storage,
+            id,
+            String::from("2025-01-10 13:00"),
+            String::from("2025-01-11 09:00")
+        ).expect("err");
+
+        edit_timer(
+            &storage,
+            id,
+            Some(String::from("meeting")),
+            None,
+            Some(String::from("2025-01-10 18:00")),
+            None
+        ).expect("err");
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.task, "meeting");
+        assert_eq!(timer.time_on(), 8 * 3600);
+        assert_eq!(timer.idle, 3600);
+
+        edit_timer(&storage, id, None, None, None, Some(4 * 3600)).expect("err");
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.time_on(), 5 * 3600);
+        assert_eq!(storage.get_sessions_by_timer(id).expect("err").len(), 2);
+
+        assert!(edit_timer(
+            &storage,
+            id,
+            None,
+            Some(String::from("2025-01-10 19:00")),
+            None,
+            None
+        ).is_err());
+        assert!(edit_timer(&storage, id, None, None, None, Some(10 * 3600)).is_err());
+        assert_eq!(load_timer(&storage, id).expect("err").time_on(), 5 * 3600);
+    }
+
+    #[test]
+    fn test_switch() {
+        let storage = Storage::from_memory().expect("err");
+        let first = new_timer(&storage, String::from("first"), None, vec![]).expect("err");
+        let second = new_timer(&storage, String::from("second"), None, vec![]).expect("err");
+        start_timer(&storage, first).expect("err");
+
+        switch_timer(&storage, second).expect("err");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::RUN);
+
+        let third = switch_new_timer(&storage, String::from("third"), None, vec![]).expect("err");
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, third).expect("err").status, TimerStatus::RUN);
+
+        complete_timer(&storage, first).expect("err");
+        assert!(switch_timer(&storage, first).is_err());
+        assert_eq!(load_timer(&storage, third).expect("err").status, TimerStatus::RUN);
+        assert_eq!(storage.count_timers_by_status(TimerStatus::RUN as u32).expect("err"), 1);
+    }
+
+    #[test]
+    fn test_undo_switch() {
+        let storage = MemoryStore::new();
+        let first = new_timer(&storage, String::from("first"), None, vec![]).expect("err");
+        let second = new_timer(&storage, String::from("second"), None, vec![]).expect("err");
+        start_timer(&storage, first).expect("err");
+        switch_timer(&storage, second).expect("err");
+
+        assert_eq!(undo(&storage).expect("err").unwrap().timer_id, second);
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::RUN);
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::NEW);
+        assert_eq!(redo(&storage).expect("err").unwrap().timer_id, second);
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::RUN);
+
+        let storage = Storage::from_memory().expect("err");
+        let first = new_timer(&storage, String::from("first"), None, vec![]).expect("err");
+        start_timer(&storage, first).expect("err");
+        let second = switch_new_timer(&storage, String::from("second"), None, vec![]).expect("err");
+        undo(&storage).expect("err");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::RUN);
+        assert!(storage.get_timer_by_id(second).is_err());
+        assert_eq!(undo(&storage).expect("err").unwrap().action, "start");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::NEW);
+        redo(&storage).expect("err");
+        redo(&storage).expect("err");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, second).expect("err").status, TimerStatus::RUN);
+        assert!(redo(&storage).expect("err").is_none());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        start_timer(&storage, id).expect("err");
+        complete_timer(&storage, id).expect("err");
+        delete_timer(&storage, id).expect("err");
+
+        assert_eq!(undo(&storage).expect("err").unwrap().action, "delete");
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::COMPLETED);
+        assert_eq!(undo(&storage).expect("err").unwrap().action, "complete");
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::RUN);
+        undo(&storage).expect("err");
+        let timer = load_timer(&storage, id).expect("err");
         assert_eq!(timer.status, TimerStatus::NEW);
+        assert!(timer.sessions.is_empty());
+        undo(&storage).expect("err");
+        assert!(storage.get_timer_by_id(id).is_err());
+        assert!(undo(&storage).expect("err").is_none());
+
+        assert_eq!(redo(&storage).expect("err").unwrap().action, "new");
+        assert_eq!(redo(&storage).expect("err").unwrap().action, "start");
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.status, TimerStatus::RUN);
+        assert_eq!(timer.sessions.len(), 1);
+
+        stop_timer(&storage, id).expect("err");
+        assert!(redo(&storage).expect("err").is_none());
+        assert_eq!(undo(&storage).expect("err").unwrap().action, "stop");
+    }
+
+    #[test]
+    fn test_reopen() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        assert!(reopen_timer(&storage, id).is_err());
+        complete_timer(&storage, id).expect("err");
+        reopen_timer(&storage, id).expect("err");
+        start_timer(&storage, id).expect("err");
+
+        let row = storage.get_timer_by_id(id).expect("err");
+        assert_eq!(row.status, TimerStatus::RUN as u32);
+        assert!(row.completed_at.is_some());
+        assert!(row.reopened_at.is_some());
+    }
+
+    #[test]
+    fn test_trash() {
+        let storage = Storage::from_memory().expect("err");
+        let first = new_timer(&storage, String::from("first"), None, vec![]).expect("err");
+        let second = new_timer(&storage, String::from("second"), None, vec![]).expect("err");
+        start_timer(&storage, first).expect("err");
+        delete_timer(&storage, first).expect("err");
+        delete_timer(&storage, second).expect("err");
+
+        restore_timer(&storage, first).expect("err");
+        assert_eq!(load_timer(&storage, first).expect("err").status, TimerStatus::PAUSED);
+        assert!(restore_timer(&storage, first).is_err());
+
+        assert_eq!(purge_timers(&storage, Some(String::from("1d"))).expect("err"), 0);
+        assert_eq!(purge_timers(&storage, None).expect("err"), 1);
+        assert!(storage.get_timer_by_id(second).is_err());
+        assert!(purge_timers(&storage, Some(String::from("30x"))).is_err());
+    }
+
+    #[test]
+    fn test_purge_long_ages() {
+        let storage = MemoryStore::new();
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        delete_timer(&storage, id).expect("err");
+        assert_eq!(purge_timers(&storage, Some(String::from("3000w"))).expect("err"), 0);
+        assert!(purge_timers(&storage, Some(String::from("9223372036854775807m"))).is_err());
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::DELETED);
+
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        delete_timer(&storage, id).expect("err");
+        assert_eq!(purge_timers(&storage, Some(String::from("3000w"))).expect("err"), 0);
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::DELETED);
+    }
+
+    #[test]
+    fn test_rollup() {
+        let storage = MemoryStore::new();
+        let day = | from: &str, to: &str | {
+            let id = add_timer(
+                &storage,
+                String::from("task"),
+                None,
+                vec![String::from("review")],
+                from.to_string(),
+                to.to_string()
+            ).expect("err");
+            complete_timer(&storage, id).expect("err");
+            id
+        };
+        day("2025-01-10 10:00", "2025-01-10 10:30");
+        day("2025-01-10 14:00", "2025-01-10 14:45");
+        let single = day("2025-01-11 10:00", "2025-01-11 11:00");
+        let recent = day("2025-03-01 10:00", "2025-03-01 11:00");
+
+        let before = parse_time(String::from("2025-02-01")).unwrap().timestamp() as u64;
+        assert_eq!(rollup_timers(&storage, before).expect("err"), 2);
+        let rows = storage.get_timers_by_status(TimerStatus::COMPLETED as u32, -1).expect("err");
+        assert_eq!(rows.len(), 3);
+        let total = rows.iter().find(| r | r.task == "Tasks of 2025-01-10").unwrap();
+        assert_eq!(load_timer(&storage, total.id).expect("err").time_on(), 75 * 60);
+        assert!(storage.get_tags_by_timer(total.id).expect("err").is_empty());
+        assert!(storage.get_timer_by_id(single).is_ok());
+        assert!(storage.get_timer_by_id(recent).is_ok());
+        assert_eq!(rollup_timers(&storage, before).expect("err"), 0);
+    }
+
+    #[test]
+    fn test_rollup_midnight() {
+        let storage = Storage::from_memory().expect("err");
+        for (from, to) in [
+            ("2025-01-10 10:00", "2025-01-10 11:00"),
+            ("2025-01-10 23:00", "2025-01-11 01:00"),
+            ("2025-01-11 10:00", "2025-01-11 10:30"),
+            ("2025-01-12 10:00", "2025-01-12 11:00")
+        ] {
+            let id = add_past(&storage, "task", from, to);
+            complete_timer(&storage, id).expect("err");
+        }
+        let before = parse_time(String::from("2025-02-01")).unwrap().timestamp() as u64;
+        assert_eq!(rollup_timers(&storage, before).expect("err"), 3);
+        let rows = storage.get_timers_by_status(TimerStatus::COMPLETED as u32, -1).expect("err");
+        let time_of = | task: &str | {
+            let row = rows.iter().find(| r | r.task == task).unwrap();
+            load_timer(&storage, row.id).expect("err").time_on()
+        };
+        assert_eq!(rows.len(), 3);
+        assert_eq!(time_of("Tasks of 2025-01-10"), 120 * 60);
+        assert_eq!(time_of("Tasks of 2025-01-11"), 90 * 60);
+        assert_eq!(time_of("task"), 60 * 60);
+    }
+
+    #[test]
+    fn test_migrate_dry_run() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_dry_run_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("err");
+        let path = dir.join("database.db");
+        assert!(migrate(path.clone(), true).is_err());
+        assert!(!path.exists());
+
+        let conn = rusqlite::Connection::open(&path).expect("err");
+        conn.execute_batch(migrations::MIGRATIONS[0].sql).expect("err");
+        conn.execute_batch("INSERT INTO db_params (param, value_int) VALUES ('version', 1)").expect("err");
+        drop(conn);
+        let version = || Storage::open(path.clone()).expect("err").version().expect("err");
+        migrate(path.clone(), true).expect("err");
+        assert_eq!(version(), 1);
+        migrate(path.clone(), false).expect("err");
+        assert_eq!(version(), SCHEMA_VERSION);
+        fs::remove_dir_all(&dir).expect("err");
+    }
+
+    fn add_past(storage: &impl TimerStore, task: &str, from: &str, to: &str) -> i64 {
+        add_timer(storage, String::from(task), None, vec![], String::from(from), String::from(to))
+            .expect("err")
+    }
+
+    #[test]
+    fn test_sync_purged() {
+        let a = Storage::from_memory().expect("err");
+        let b = MemoryStore::new();
+        let id = add_past(&a, "purged", "2025-01-01 10:00:00", "2025-01-01 11:00:00");
+        add_past(&a, "kept", "2025-01-02 10:00:00", "2025-01-02 11:00:00");
+        sync::sync(&a, &b).expect("err");
+        assert_eq!(b.get_timers_by_date(-1, &TimerFilter::default()).expect("err").len(), 2);
+
+        delete_timer(&a, id).expect("err");
+        purge_timers(&a, None).expect("err");
+        let report = sync::sync(&a, &b).expect("err");
+        assert_eq!((report.copied, report.deleted), (0, 1));
+        assert_eq!(a.get_timers_by_date(-1, &TimerFilter::default()).expect("err").len(), 1);
+        assert_eq!(b.get_timers_by_date(-1, &TimerFilter::default()).expect("err").len(), 1);
+        let report = sync::sync(&a, &b).expect("err");
+        assert_eq!((report.copied, report.deleted), (0, 0));
+    }
+
+    #[test]
+    fn test_sync_edited() {
+        let a = MemoryStore::new();
+        let b = Storage::from_memory().expect("err");
+        let id = add_past(&a, "task", "2025-01-01 10:00:00", "2025-01-01 11:00:00");
+        add_session(&a, id, String::from("2025-01-01 12:00:00"), String::from("2025-01-01 13:00:00"))
+            .expect("err");
+        sync::sync(&a, &b).expect("err");
+
+        edit_timer(&a, id, None, None, Some(String::from("2025-01-01 11:00:00")), None).expect("err");
+        let report = sync::sync(&a, &b).expect("err");
+        assert_eq!((report.sessions, report.deleted), (0, 1));
+        let uuid = a.get_timer_by_id(id).expect("err").uuid;
+        let copy = b.get_timer_by_uuid(&uuid).expect("err");
+        assert_eq!(a.get_sessions_by_timer(id).expect("err").len(), 1);
+        assert_eq!(b.get_sessions_by_timer(copy.id).expect("err").len(), 1);
+        assert_eq!(copy.end, a.get_timer_by_id(id).expect("err").end);
+    }
+
+    #[test]
+    fn test_sync_rollup() {
+        let a = Storage::from_memory().expect("err");
+        let b = Storage::from_memory().expect("err");
+        for task in ["first", "second"] {
+            let id = add_past(&a, task, "2025-01-01 10:00:00", "2025-01-01 11:00:00");
+            complete_timer(&a, id).expect("err");
+        }
+        sync::sync(&a, &b).expect("err");
+
+        assert_eq!(rollup_timers(&a, Utc::now().timestamp() as u64).expect("err"), 2);
+        let report = sync::sync(&a, &b).expect("err");
+        assert_eq!((report.copied, report.deleted), (1, 2));
+        for store in [&a, &b] {
+            let rows = store.get_timers_by_date(-1, &TimerFilter::default()).expect("err");
+            assert_eq!(rows.len(), 1);
+            assert_eq!(load_collection(store, rows).expect("err").items()[0].time_on(), 7200);
+        }
+    }
+
+    #[test]
+    fn test_compact() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        delete_timer(&storage, id).expect("err");
+        let row = storage.get_timer_by_id(id).expect("err");
+        storage.update_timer(&SQLTimerRow { deleted_at: Some(100), ..row }).expect("err");
+        assert!(!auto_compact(&storage).expect("err"));
+        assert!(set_retention(&storage, Some(String::from("1x")), None, false).is_err());
+
+        set_retention(&storage, Some(String::from("0d")), None, false).expect("err");
+        assert!(auto_compact(&storage).expect("err"));
+        assert!(storage.get_timer_by_id(id).is_err());
+        assert!(!auto_compact(&storage).expect("err"));
+
+        set_retention(&storage, None, None, true).expect("err");
+        assert_eq!(storage.param_str(RETENTION_DELETED).expect("err"), None);
+
+        for task in ["first", "second"] {
+            let id = add_timer(
+                &storage,
+                String::from(task),
+                None,
+                vec![],
+                String::from("2025-01-01 10:00:00"),
+                String::from("2025-01-01 11:00:00")
+            ).expect("err");
+            complete_timer(&storage, id).expect("err");
+        }
+        assert!(compact_db(&storage, None, Some(String::from("20000000000000w"))).is_err());
+        compact_db(&storage, None, Some(String::from("3000w"))).expect("err");
+        assert_eq!(storage.get_timers_by_status(TimerStatus::COMPLETED as u32, -1).expect("err").len(), 2);
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d").unwrap(), 30 * 86400);
+        assert_eq!(parse_age("2w").unwrap(), 14 * 86400);
+        assert_eq!(parse_age("12h").unwrap(), 12 * 3600);
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("-1d").is_err());
+        assert!(parse_age("10").is_err());
+        assert!(matches!(parse_age("9223372036854775807w"), Err(LogicError::DurationTooLong)));
+        assert!(age_cutoff("20000000000000w").is_err());
+        assert_eq!(age_cutoff("3000w").unwrap(), 0);
+        assert!(age_cutoff("1d").unwrap() > 0);
+    }
+
+    #[test]
+    fn test_pomodoro() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        let config = PomodoroConfig { cycles: 3, ..PomodoroConfig::default() };
+        let mut phases = Vec::new();
+        let done = run_pomodoro(&storage, id, &config, | p | phases.push(*p)).expect("err");
+        assert_eq!(done, 3);
+        assert_eq!(phases.len(), 5);
+        assert_eq!(storage.get_pomodoros_by_timer(id).expect("err").len(), 3);
+        let timer = load_timer(&storage, id).expect("err");
+        assert_eq!(timer.status, TimerStatus::PAUSED);
+        assert_eq!(timer.sessions.len(), 3);
+
+        undo(&storage).expect("err");
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::RUN);
+    }
+
+    #[test]
+    fn test_pomodoro_stopped() {
+        let storage = Storage::from_memory().expect("err");
+        let id = new_timer(&storage, String::from("test"), None, vec![]).expect("err");
+        let other = new_timer(&storage, String::from("other"), None, vec![]).expect("err");
+        let config = PomodoroConfig { cycles: 3, ..PomodoroConfig::default() };
+        let mut works = 0;
+        let done = run_pomodoro(&storage, id, &config, | p | {
+            if let Phase::Work(_) = p {
+                works += 1;
+                if works == 2 {
+                    switch_timer(&storage, other).expect("err");
+                }
+            }
+        }).expect("err");
+        assert_eq!(done, 1);
+        assert_eq!(storage.get_pomodoros_by_timer(id).expect("err").len(), 1);
+        assert_eq!(load_timer(&storage, id).expect("err").status, TimerStatus::PAUSED);
+        assert_eq!(load_timer(&storage, other).expect("err").status, TimerStatus::RUN);
     }
 }
@@ -1,31 +1,220 @@
-use clap::{Parser, Subcommand};
-use focus_timer;
-use focus_timer::Storage;
+use clap::{Args, Parser, Subcommand};
+use focus_timer::{
+    Storage,
+    TimerFilter,
+    TimerPage,
+    Period,
+    SheetRows,
+    SheetFormat,
+    Profiles,
+    BACKUPS_TO_KEEP
+};
 use std::path::PathBuf;
-use std::fs;
-use dirs;
+use std::thread;
+use std::time::Duration;
 
 
-fn get_default_db_path() -> PathBuf {
+fn get_app_dir() -> PathBuf {
     if let Some(data_dir) = dirs::data_local_dir() {
-        let app_dir = data_dir.join("focus_timer");
-        return app_dir.join("database.db");
+        return data_dir.join("focus_timer");
     }
-    PathBuf::from("database.db")
+    PathBuf::from(".")
 }
 
 
+#[derive(Args)]
+struct FilterArgs {
+    #[arg(long)]
+    date_from: Option<String>,
+
+    #[arg(long)]
+    date_to: Option<String>,
+
+    #[arg(long)]
+    project: Option<String>,
+
+    #[arg(long)]
+    tag: Vec<String>,
+
+    #[arg(long)]
+    exclude_tag: Vec<String>
+}
+
+impl From<&FilterArgs> for TimerFilter {
+    fn from(args: &FilterArgs) -> Self {
+        TimerFilter {
+            date_from: args.date_from.clone(),
+            date_to: args.date_to.clone(),
+            project: args.project.clone(),
+            tags_include: args.tag.clone(),
+            tags_exclude: args.exclude_tag.clone()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TagCommands {
+    Add {
+        #[arg(short, long)]
+        id: i64,
+
+        tag: String
+    },
+    Remove {
+        #[arg(short, long)]
+        id: i64,
+
+        tag: String
+    }
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    List
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    List,
+    Restore {
+        file: String
+    }
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    Migrate {
+        #[arg(long)]
+        dry_run: bool
+    },
+    Check {
+        #[arg(long)]
+        fix: bool
+    },
+    Compact {
+        #[arg(long)]
+        older_than: Option<String>,
+
+        #[arg(long)]
+        rollup: Option<String>
+    },
+    Retention {
+        #[arg(long)]
+        deleted: Option<String>,
+
+        #[arg(long)]
+        rollup: Option<String>,
+
+        #[arg(long)]
+        off: bool
+    }
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    List,
+    Create {
+        name: String
+    },
+    Use {
+        name: String
+    },
+    Remove {
+        name: String
+    }
+}
+
+#[derive(Subcommand)]
+enum ProjectCommands {
+    Add {
+        name: String
+    },
+    List {
+        #[arg(long)]
+        all: bool
+    },
+    Rename {
+        name: String,
+        new_name: String
+    },
+    Archive {
+        name: String
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Info,
     New { 
         #[arg(short, long)]
-        task: String
+        task: String,
+
+        #[arg(long)]
+        project: Option<String>,
+
+        #[arg(long)]
+        tag: Vec<String>
+    },
+    Add {
+        #[arg(short, long, required_unless_present = "id")]
+        task: Option<String>,
+
+        #[arg(short, long, conflicts_with_all = ["task", "project", "tag"])]
+        id: Option<i64>,
+
+        #[arg(long)]
+        from: String,
+
+        #[arg(long)]
+        to: String,
+
+        #[arg(long)]
+        project: Option<String>,
+
+        #[arg(long)]
+        tag: Vec<String>
+    },
+    Edit {
+        #[arg(short, long)]
+        id: i64,
+
+        #[arg(short, long)]
+        task: Option<String>,
+
+        #[arg(long)]
+        start: Option<String>,
+
+        #[arg(long)]
+        end: Option<String>,
+
+        #[arg(long)]
+        idle: Option<i64>
+    },
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommands
+    },
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands
     },
     Start {
         #[arg(short, long)]
         id: i64
     },
+    Switch {
+        #[arg(short, long, required_unless_present = "task")]
+        id: Option<i64>,
+
+        #[arg(short, long, conflicts_with = "id")]
+        task: Option<String>,
+
+        #[arg(long, requires = "task")]
+        project: Option<String>,
+
+        #[arg(long, requires = "task")]
+        tag: Vec<String>
+    },
     Stop {
         #[arg(short, long)]
         id: i64
@@ -34,73 +223,346 @@ enum Commands {
         #[arg(short, long)]
         id: i64
     },
+    Reopen {
+        #[arg(short, long)]
+        id: i64
+    },
     Delete {
         #[arg(short, long)]
         id: i64
     },
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands
+    },
+    Restore {
+        #[arg(short, long)]
+        id: i64
+    },
+    Purge {
+        #[arg(long)]
+        older_than: Option<String>
+    },
+    Pomodoro {
+        #[arg(short, long)]
+        id: i64,
+
+        #[arg(long, default_value_t = 25)]
+        work: u64,
+
+        #[arg(long, default_value_t = 5)]
+        short_break: u64,
+
+        #[arg(long, default_value_t = 15)]
+        long_break: u64,
+
+        #[arg(long, default_value_t = 4)]
+        long_break_every: u32,
+
+        #[arg(long, short, default_value_t = 4)]
+        cycles: u32
+    },
+    Sessions {
+        #[arg(short, long)]
+        id: i64
+    },
+    Undo,
+    Redo,
     Last { 
         #[arg(short)]
         n: u64
     },
-    Flush,
-    List {
+    Flush {
         #[arg(long)]
-        date_from: Option<String>,
+        no_backup: bool
+    },
+    Backup {
+        #[command(subcommand)]
+        command: Option<BackupCommands>,
 
-        #[arg(long)]
-        date_to: Option<String>,
+        #[arg(
+            long,
+            default_value_t = BACKUPS_TO_KEEP,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        keep: usize
+    },
+    Db {
+        #[command(subcommand)]
+        command: DbCommands
+    },
+    Sync {
+        path: PathBuf
+    },
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands
+    },
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
 
         #[arg(long, short)]
         n: Option<i32>
     },
-    Export {
-        #[arg(long)]
-        date_from: Option<String>,
+    List {
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[arg(long, short)]
+        n: Option<i32>,
+
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "after_id")]
+        page: Option<u64>,
 
         #[arg(long)]
-        date_to: Option<String>,
+        after_id: Option<i64>
+    },
+    Export {
+        #[command(flatten)]
+        filter: FilterArgs,
 
         #[arg(short, long)]
         path: String
     },
     Stat {
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[arg(long)]
+        by_tag: bool,
+
+        #[arg(long)]
+        chart: bool,
+
+        #[arg(long, default_value_t = 14, value_parser = clap::value_parser!(u64).range(1..=366))]
+        days: u64
+    },
+    Report {
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[arg(long, default_value = "day")]
+        by: Period
+    },
+    Heatmap {
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1970..=9999))]
+        year: Option<i32>,
+
+        #[arg(long)]
+        project: Option<String>,
+
         #[arg(long)]
-        date_from: Option<String>,
+        tag: Vec<String>
+    },
+    Timesheet {
+        #[arg(long)]
+        week: Option<String>,
+
+        #[arg(long, default_value = "task")]
+        by: SheetRows,
+
+        #[arg(long, default_value = "text")]
+        format: SheetFormat,
+
+        #[arg(long)]
+        project: Option<String>,
+
+        #[arg(long)]
+        tag: Vec<String>,
 
         #[arg(long)]
-        date_to: Option<String>,
+        exclude_tag: Vec<String>
+    }
+}
+
+impl Commands {
+    /// Commands which change timers, only these run the automatic compaction.
+    fn writes_data(&self) -> bool {
+        match self {
+            Commands::New { .. } | Commands::Add { .. } | Commands::Edit { .. } | Commands::Tag { .. }
+                | Commands::Start { .. } | Commands::Switch { .. } | Commands::Stop { .. }
+                | Commands::Complete { .. } | Commands::Reopen { .. } | Commands::Delete { .. }
+                | Commands::Restore { .. } | Commands::Purge { .. } | Commands::Pomodoro { .. }
+                | Commands::Undo | Commands::Redo | Commands::Sync { .. } => true,
+            Commands::Project { command } => !matches!(command, ProjectCommands::List { .. }),
+            _ => false
+        }
     }
 }
 
 #[derive(Parser)]
 struct Cli {
     #[command(subcommand)]
-    command: Option<Commands>
+    command: Option<Commands>,
+
+    #[arg(long, global = true)]
+    profile: Option<String>
+}
+
+fn run_profile_command(profiles: &Profiles, command: &ProfileCommands) {
+    let result = match command {
+        ProfileCommands::List => focus_timer::show_profiles(profiles),
+        ProfileCommands::Create { name } => profiles.create(name)
+            .map(| path | println!("Created profile {name} at {}", path.display())),
+        ProfileCommands::Use { name } => profiles.use_profile(name)
+            .map(| _ | println!("Using profile {name}")),
+        ProfileCommands::Remove { name } => profiles.remove(name)
+            .map(| _ | println!("Removed profile {name}"))
+    };
+    if let Err(e) = result {
+        panic!("{e}")
+    }
 }
 
 
 fn main() {
-    let db_path = std::env::var("APP_DB_PATH")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| get_default_db_path());
-    let storage = Storage::from_path(db_path.clone()).expect("DB not created");
     let cli = Cli::parse();
+    let profiles = Profiles::new(get_app_dir());
+    if let Some(Commands::Profile { command }) = &cli.command {
+        run_profile_command(&profiles, command);
+        return;
+    }
+    let (profile, db_path) = match (&cli.profile, std::env::var("APP_DB_PATH")) {
+        (Some(name), _) => match profiles.existing_db_path(name) {
+            Ok(path) => (Some(name.clone()), path),
+            Err(e) => panic!("{e}")
+        },
+        (None, Ok(path)) => (None, PathBuf::from(path)),
+        (None, Err(_)) => {
+            let name = profiles.active();
+            let path = profiles.db_path(&name);
+            (Some(name), path)
+        }
+    };
+    if let Some(Commands::Db { command: DbCommands::Migrate { dry_run } }) = &cli.command {
+        if let Err(e) = focus_timer::migrate(db_path, *dry_run) {
+            panic!("{e}")
+        }
+        return;
+    }
+    let mut storage = Storage::from_path(db_path.clone()).expect("DB not created");
+    if cli.command.as_ref().is_some_and(Commands::writes_data) {
+        if let Err(e) = focus_timer::auto_compact(&storage) {
+            panic!("{e}")
+        }
+    }
     match &cli.command {
         Some(Commands::Info) => {
+            if let Some(name) = profile {
+                println!("Profile: {name}");
+            }
             println!("Using database at: {}", db_path.display())
         },
-        Some(Commands::New { task }) => {
-            match focus_timer::new_timer(&storage, task.to_string()) {
+        Some(Commands::New { task, project, tag }) => {
+            match focus_timer::new_timer(
+                &storage,
+                task.to_string(),
+                project.clone(),
+                tag.clone()
+            ) {
                 Ok(id) => println!("Created timer {}", id),
                 Err(e) => panic!("{e}")
             };
         },
+        Some(Commands::Add { task, id, from, to, project, tag }) => {
+            let result = match (task, id) {
+                (_, Some(id)) => {
+                    focus_timer::add_session(&storage, *id, from.clone(), to.clone())
+                        .map(| _ | println!("Session is added to timer {id}"))
+                },
+                (Some(task), None) => {
+                    focus_timer::add_timer(
+                        &storage,
+                        task.clone(),
+                        project.clone(),
+                        tag.clone(),
+                        from.clone(),
+                        to.clone()
+                    ).map(| id | println!("Created timer {id}"))
+                },
+                (None, None) => unreachable!()
+            };
+            if let Err(e) = result {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Edit { id, task, start, end, idle }) => {
+            match focus_timer::edit_timer(
+                &storage,
+                *id,
+                task.clone(),
+                start.clone(),
+                end.clone(),
+                *idle
+            ) {
+                Ok(()) => println!("Task is updated"),
+                Err(e) => panic!("{e}")
+            };
+        },
+        Some(Commands::Project { command }) => {
+            let result = match command {
+                ProjectCommands::Add { name } => {
+                    focus_timer::add_project(&storage, name.clone())
+                        .map(| id | println!("Created project {id}"))
+                },
+                ProjectCommands::List { all } => {
+                    focus_timer::show_projects(&storage, *all)
+                },
+                ProjectCommands::Rename { name, new_name } => {
+                    focus_timer::rename_project(&storage, name.clone(), new_name.clone())
+                        .map(| _ | println!("Project is renamed"))
+                },
+                ProjectCommands::Archive { name } => {
+                    focus_timer::archive_project(&storage, name.clone())
+                        .map(| _ | println!("Project is archived"))
+                }
+            };
+            if let Err(e) = result {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Tag { command }) => {
+            let result = match command {
+                TagCommands::Add { id, tag } => {
+                    focus_timer::add_tag(&storage, *id, tag.clone())
+                        .map(| _ | println!("Tag is added"))
+                },
+                TagCommands::Remove { id, tag } => {
+                    focus_timer::remove_tag(&storage, *id, tag.clone())
+                        .map(| _ | println!("Tag is removed"))
+                }
+            };
+            if let Err(e) = result {
+                panic!("{e}")
+            }
+        },
         Some(Commands::Start { id }) => {
             match focus_timer::start_timer(&storage, *id) {
                 Ok(()) => println!("Task started"),
                 Err(e) => panic!("{e}")
             };
         },
+        Some(Commands::Switch { id, task, project, tag }) => {
+            let result = match (id, task) {
+                (Some(id), _) => {
+                    focus_timer::switch_timer(&storage, *id)
+                        .map(| _ | println!("Switched to task {id}"))
+                },
+                (None, Some(task)) => {
+                    focus_timer::switch_new_timer(
+                        &storage,
+                        task.clone(),
+                        project.clone(),
+                        tag.clone()
+                    ).map(| id | println!("Created timer {id} and switched to it"))
+                },
+                (None, None) => unreachable!()
+            };
+            if let Err(e) = result {
+                panic!("{e}")
+            }
+        },
         Some(Commands::Stop { id }) => {
             match focus_timer::stop_timer(&storage, *id) {
                 Ok(()) => println!("Task is paused"),
@@ -113,50 +575,206 @@ fn main() {
                 Err(e) => panic!("{e}")
             };
         },
+        Some(Commands::Reopen { id }) => {
+            match focus_timer::reopen_timer(&storage, *id) {
+                Ok(()) => println!("Task is reopened"),
+                Err(e) => panic!("{e}")
+            };
+        },
         Some(Commands::Delete { id }) => {
             match focus_timer::delete_timer(&storage, *id) {
-                Ok(()) => println!("Task is completed"),
+                Ok(()) => println!("Task is moved to trash"),
                 Err(e) => panic!("{e}")
             };
         },
-        Some(Commands::Flush) => {
-            match fs::remove_file(db_path) {
+        Some(Commands::Flush { no_backup }) => {
+            if !no_backup {
+                match focus_timer::backup(&storage, BACKUPS_TO_KEEP) {
+                    Ok(path) => println!("Backup is saved to {}", path.display()),
+                    Err(e) => panic!("{e}")
+                }
+            }
+            // closing the connection folds the WAL file back into the database
+            drop(storage);
+            match focus_timer::remove_database(&db_path) {
                 Err(e) => panic!("{e}"),
                 _ => println!("Database was deleted")
             }
         },
-        Some(Commands::List { date_from, date_to, n }) => {
+        Some(Commands::Backup { command: None, keep }) => {
+            match focus_timer::backup(&storage, *keep) {
+                Ok(path) => println!("Backup is saved to {}", path.display()),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Backup { command: Some(BackupCommands::List), .. }) => {
+            if let Err(e) = focus_timer::show_backups(&storage) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Backup { command: Some(BackupCommands::Restore { file }), keep }) => {
+            match focus_timer::restore_backup(&mut storage, file, *keep) {
+                Ok(path) => println!("Database is restored, previous state is saved to {}", path.display()),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Sync { path }) => {
+            if let Err(e) = focus_timer::sync_db(&storage, path.clone()) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Search { query, n }) => {
+            match focus_timer::search(&storage, &query.join(" "), n.unwrap_or(20)) {
+                Ok(()) => {},
+                Err(e) => panic!("{e}")
+            };
+        },
+        Some(Commands::List { filter, n, page, after_id }) => {
+            let paged = page.is_some() || after_id.is_some();
+            let limit = n.unwrap_or(if paged { 20 } else { -1 });
+            let offset = page.map(| p | (p - 1) * limit.max(0) as u64).unwrap_or(0);
             match focus_timer::show_list(
                 &storage,
-                n.unwrap_or(-1),
-                date_from.clone(),
-                date_to.clone()
+                TimerPage { limit, offset, after_id: *after_id },
+                TimerFilter::from(filter)
             ) {
                 Ok(()) => {},
                 Err(e) => panic!("{e}")
             };
         },
-        Some(Commands::Export { date_from, date_to, path }) => {
+        Some(Commands::Export { filter, path }) => {
             match focus_timer::export(
                 &storage,
                 path.clone(),
-                date_from.clone(),
-                date_to.clone()
+                TimerFilter::from(filter)
             ) {
                 Ok(()) => {},
                 Err(e) => panic!("{e}")
             };
         },
-        Some(Commands::Stat { date_from, date_to }) => {
+        Some(Commands::Stat { filter, by_tag, chart, days }) => {
             match focus_timer::show_stat(
                 &storage,
-                date_from.clone(),
-                date_to.clone()
+                TimerFilter::from(filter),
+                *by_tag,
+                chart.then_some(*days as usize)
             ) {
                 Ok(()) => {},
                 Err(e) => panic!("{e}")
             };
         },
+        Some(Commands::Report { filter, by }) => {
+            if let Err(e) = focus_timer::show_report(&storage, TimerFilter::from(filter), *by) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Timesheet { week, by, format, project, tag, exclude_tag }) => {
+            let filter = TimerFilter {
+                project: project.clone(),
+                tags_include: tag.clone(),
+                tags_exclude: exclude_tag.clone(),
+                ..TimerFilter::default()
+            };
+            if let Err(e) = focus_timer::show_timesheet(&storage, filter, week.clone(), *by, *format) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Heatmap { year, project, tag }) => {
+            let filter = TimerFilter {
+                project: project.clone(),
+                tags_include: tag.clone(),
+                ..TimerFilter::default()
+            };
+            if let Err(e) = focus_timer::show_heatmap(&storage, filter, *year) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Trash { command: TrashCommands::List }) => {
+            if let Err(e) = focus_timer::show_trash(&storage) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Restore { id }) => {
+            match focus_timer::restore_timer(&storage, *id) {
+                Ok(()) => println!("Task is restored"),
+                Err(e) => panic!("{e}")
+            };
+        },
+        Some(Commands::Purge { older_than }) => {
+            match focus_timer::purge_timers(&storage, older_than.clone()) {
+                Ok(n) => println!("{n} deleted tasks are purged"),
+                Err(e) => panic!("{e}")
+            };
+        },
+        Some(Commands::Pomodoro {
+            id,
+            work,
+            short_break,
+            long_break,
+            long_break_every,
+            cycles
+        }) => {
+            let config = focus_timer::PomodoroConfig {
+                work: Duration::from_secs(work * 60),
+                short_break: Duration::from_secs(short_break * 60),
+                long_break: Duration::from_secs(long_break * 60),
+                long_break_every: *long_break_every,
+                cycles: *cycles
+            };
+            match focus_timer::run_pomodoro(
+                &storage,
+                *id,
+                &config,
+                | phase | thread::sleep(phase.duration())
+            ) {
+                Ok(n) => println!("{n} pomodoros completed"),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Sessions { id }) => {
+            match focus_timer::show_sessions(&storage, *id) {
+                Ok(()) => {},
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Undo) => {
+            match focus_timer::undo(&storage) {
+                Ok(Some(e)) => println!("Undone: {} of task {}", e.action, e.timer_id),
+                Ok(None) => println!("Nothing to undo"),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Redo) => {
+            match focus_timer::redo(&storage) {
+                Ok(Some(e)) => println!("Redone: {} of task {}", e.action, e.timer_id),
+                Ok(None) => println!("Nothing to redo"),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Db { command: DbCommands::Migrate { .. } }) => unreachable!(),
+        Some(Commands::Profile { .. }) => unreachable!(),
+        Some(Commands::Db { command: DbCommands::Check { fix } }) => {
+            match focus_timer::check_db(&storage, *fix) {
+                Ok(0) => {},
+                Ok(_) => std::process::exit(1),
+                Err(e) => panic!("{e}")
+            }
+        },
+        Some(Commands::Db { command: DbCommands::Compact { older_than, rollup } }) => {
+            if let Err(e) = focus_timer::compact_db(&storage, older_than.clone(), rollup.clone()) {
+                panic!("{e}")
+            }
+        },
+        Some(Commands::Db { command: DbCommands::Retention { deleted, rollup, off } }) => {
+            if let Err(e) = focus_timer::set_retention(
+                &storage,
+                deleted.clone(),
+                rollup.clone(),
+                *off
+            ) {
+                panic!("{e}")
+            }
+        },
         Some(Commands::Last { n }) => {
             match focus_timer::show_last_n(&storage, *n) {
                 Ok(()) => {},
@@ -0,0 +1,695 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::storage::{
+    Storage,
+    SQLTimerRow,
+    SQLSessionRow,
+    SQLPomodoroRow,
+    SQLProjectRow,
+    SQLEventRow,
+    SQLDeletedRow,
+    TimerFilter,
+    TimerPage,
+    TimerSnapshot,
+    StorageError,
+    DELETED_TIMER,
+    DELETED_SESSION
+};
+use crate::store::{TimerStore, now, uuid_or_new};
+
+
+/// Largest ids given so far, ids are not reused after removal
+/// just like AUTOINCREMENT columns of the SQLite storage.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LastIds {
+    timer: i64,
+    session: i64,
+    pomodoro: i64,
+    event: i64,
+    project: i64
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoreData {
+    last_ids: LastIds,
+    timers: Vec<SQLTimerRow>,
+    sessions: Vec<SQLSessionRow>,
+    pomodoros: Vec<SQLPomodoroRow>,
+    timer_tags: Vec<(i64, String)>,
+    events: Vec<SQLEventRow>,
+    projects: Vec<SQLProjectRow>,
+    #[serde(default)]
+    deleted: Vec<SQLDeletedRow>
+}
+
+impl StoreData {
+
+    /// Records the timers `ids` and their sessions as deleted.
+    fn record_deleted_timers(&mut self, ids: &[i64]) {
+        let uuids: Vec<(&str, String)> = self.sessions.iter()
+            .filter(| s | ids.contains(&s.timer_id))
+            .map(| s | (DELETED_SESSION, s.uuid.clone()))
+            .chain(self.timers.iter()
+                .filter(| t | ids.contains(&t.id))
+                .map(| t | (DELETED_TIMER, t.uuid.clone())))
+            .collect();
+        for (kind, uuid) in uuids {
+            self.record_deleted(kind, uuid);
+        }
+    }
+
+    fn record_deleted(&mut self, kind: &str, uuid: String) {
+        if uuid.is_empty() {
+            return;
+        }
+        self.forget_deleted(&uuid);
+        self.deleted.push(SQLDeletedRow { uuid, kind: kind.to_string(), deleted_at: now() });
+    }
+
+    fn forget_deleted(&mut self, uuid: &str) {
+        self.deleted.retain(| d | d.uuid != uuid);
+    }
+}
+
+/// Returns `id` or the next free one when it is 0.
+fn next_id(last: &mut i64, id: i64) -> i64 {
+    let id = if id == 0 { *last + 1 } else { id };
+    *last = (*last).max(id);
+    id
+}
+
+fn limited(rows: Vec<SQLTimerRow>, limit: i64) -> Vec<SQLTimerRow> {
+    if limit < 0 {
+        return rows;
+    }
+    rows.into_iter().take(limit as usize).collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(| c: char | !c.is_alphanumeric())
+        .filter(| w | !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn file_error(e: impl fmt::Display) -> StorageError {
+    StorageError::FileError(e.to_string())
+}
+
+
+/// Storage without a database: the data is kept in memory and,
+/// when opened with `from_json`, written to a JSON file after every change.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: RefCell<StoreData>,
+    path: Option<PathBuf>,
+    depth: Cell<u32>
+}
+
+impl MemoryStore {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the data from the file, it is created on the first change.
+    pub fn from_json(path: PathBuf) -> Result<Self, StorageError> {
+        let data = match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).map_err(file_error)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => StoreData::default(),
+            Err(e) => return Err(file_error(e))
+        };
+        Ok(Self { data: RefCell::new(data), path: Some(path), depth: Cell::new(0) })
+    }
+
+    /// Writes the file unless there is none or an `atomic` call is running.
+    /// It is written to a temporary file first so a failed write
+    /// does not break the old one.
+    fn save(&self) -> Result<(), StorageError> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if self.depth.get() > 0 {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(&*self.data.borrow()).map_err(file_error)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json).map_err(file_error)?;
+        fs::rename(&tmp, path).map_err(file_error)
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&StoreData) -> T) -> T {
+        f(&self.data.borrow())
+    }
+
+    fn write<T>(&self, f: impl FnOnce(&mut StoreData) -> T) -> Result<T, StorageError> {
+        let result = f(&mut self.data.borrow_mut());
+        self.save()?;
+        Ok(result)
+    }
+}
+
+impl TimerStore for MemoryStore {
+
+    /// The data is copied before `f` runs and put back if it fails.
+    fn atomic<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<StorageError> {
+        let backup = self.data.borrow().clone();
+        self.depth.set(self.depth.get() + 1);
+        let result = f();
+        self.depth.set(self.depth.get() - 1);
+        match result {
+            Ok(result) => {
+                self.save()?;
+                Ok(result)
+            },
+            Err(e) => {
+                *self.data.borrow_mut() = backup;
+                Err(e)
+            }
+        }
+    }
+
+    fn is_timer_exist(&self, id: i64) -> Result<bool, StorageError> {
+        Ok(self.read(| d | d.timers.iter().any(| t | t.id == id)))
+    }
+
+    fn insert_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            let id = next_id(&mut d.last_ids.timer, timer.id);
+            d.timers.push(SQLTimerRow {
+                id,
+                uuid: uuid_or_new(&timer.uuid),
+                updated_at: now(),
+                ..timer.clone()
+            });
+            id
+        })
+    }
+
+    fn update_timer(&self, timer: &SQLTimerRow) -> Result<(), StorageError> {
+        self.write(| d | {
+            if let Some(t) = d.timers.iter_mut().find(| t | t.id == timer.id) {
+                *t = SQLTimerRow { uuid: t.uuid.clone(), updated_at: now(), ..timer.clone() };
+            }
+        })
+    }
+
+    fn merge_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            d.forget_deleted(&timer.uuid);
+            if let Some(t) = d.timers.iter_mut().find(| t | t.uuid == timer.uuid) {
+                *t = SQLTimerRow { id: t.id, ..timer.clone() };
+                return t.id;
+            }
+            let id = next_id(&mut d.last_ids.timer, 0);
+            d.timers.push(SQLTimerRow { id, ..timer.clone() });
+            id
+        })
+    }
+
+    fn get_timer_by_uuid(&self, uuid: &str) -> Result<SQLTimerRow, StorageError> {
+        self.read(| d | d.timers.iter().find(| t | t.uuid == uuid).cloned())
+            .ok_or(StorageError::TimerDoesNotExists)
+    }
+
+    fn get_timer_by_id(&self, id: i64) -> Result<SQLTimerRow, StorageError> {
+        self.read(| d | d.timers.iter().find(| t | t.id == id).cloned())
+            .ok_or(StorageError::TimerDoesNotExists)
+    }
+
+    fn count_timers_by_status(&self, status: u32) -> Result<u64, StorageError> {
+        Ok(self.read(| d | d.timers.iter().filter(| t | t.status == status).count() as u64))
+    }
+
+    fn get_timers_by_status(
+        &self,
+        status: u32,
+        limit: i32
+    ) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let mut rows: Vec<SQLTimerRow> = self.read(| d | d.timers.iter()
+            .filter(| t | t.status == status)
+            .cloned()
+            .collect());
+        rows.sort_by_key(| t | Reverse(t.id));
+        Ok(limited(rows, limit as i64))
+    }
+
+    fn get_last_timers(&self, limit: u64) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let mut rows = self.read(| d | d.timers.clone());
+        rows.sort_by_key(| t | Reverse(t.end));
+        Ok(limited(rows, limit as i64))
+    }
+
+    fn get_timers_page(
+        &self,
+        filter: &TimerFilter,
+        page: &TimerPage
+    ) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let from_timestamp = filter.date_from.clone().map(Storage::str_to_time).transpose()?;
+        let to_timestamp = filter.date_to.clone().map(Storage::str_to_time).transpose()?;
+        let project_id = match &filter.project {
+            Some(name) => Some(self.get_project_by_name(name)?.id),
+            None => None
+        };
+        let mut rows: Vec<SQLTimerRow> = self.read(| d | {
+            let has_any = | id: i64, tags: &Vec<String> | d.timer_tags.iter()
+                .any(| (timer_id, tag) | *timer_id == id && tags.contains(tag));
+            d.timers.iter()
+                .filter(| t | from_timestamp.is_none_or(| from | t.start >= from))
+                .filter(| t | to_timestamp.is_none_or(| to | t.start < to))
+                .filter(| t | project_id.is_none() || t.project_id == project_id)
+                .filter(| t | filter.tags_include.is_empty() || has_any(t.id, &filter.tags_include))
+                .filter(| t | !has_any(t.id, &filter.tags_exclude))
+                .cloned()
+                .collect()
+        });
+        rows.sort_by_key(| t | Reverse((t.start, t.id)));
+        if let Some(id) = page.after_id {
+            let after = self.get_timer_by_id(id)?;
+            rows.retain(| t | (t.start, t.id) < (after.start, after.id));
+        }
+        let rows = rows.into_iter().skip(page.offset as usize).collect();
+        Ok(limited(rows, page.limit as i64))
+    }
+
+    /// Matching is done like in FTS5: every query word has to start
+    /// a word of the task, shorter tasks are ranked higher.
+    fn search_timers(&self, query: &str, limit: i32) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let terms: Vec<String> = words(query);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut rows: Vec<(usize, SQLTimerRow)> = self.read(| d | d.timers.iter()
+            .filter_map(| t | {
+                let task = words(&t.task);
+                terms.iter()
+                    .all(| term | task.iter().any(| w | w.starts_with(term.as_str())))
+                    .then(|| (task.len(), t.clone()))
+            })
+            .collect());
+        rows.sort_by_key(| (n, t) | (*n, Reverse(t.id)));
+        Ok(limited(rows.into_iter().map(| (_, t) | t).collect(), limit as i64))
+    }
+
+    fn purge_timers(&self, deleted_before: Option<u64>) -> Result<usize, StorageError> {
+        self.write(| d | {
+            let ids: Vec<i64> = d.timers.iter()
+                .filter(| t | t.status == 9)
+                .filter(| t | match (deleted_before, t.deleted_at) {
+                    (Some(before), Some(at)) => at < before,
+                    _ => true
+                })
+                .map(| t | t.id)
+                .collect();
+            d.record_deleted_timers(&ids);
+            d.sessions.retain(| s | !ids.contains(&s.timer_id));
+            d.timer_tags.retain(| (id, _) | !ids.contains(id));
+            d.pomodoros.retain(| p | !ids.contains(&p.timer_id));
+            d.events.retain(| e | !ids.contains(&e.timer_id));
+            d.timers.retain(| t | !ids.contains(&t.id));
+            ids.len()
+        })
+    }
+
+    fn remove_timer(&self, id: i64) -> Result<(), StorageError> {
+        self.write(| d | {
+            d.record_deleted_timers(&[id]);
+            d.sessions.retain(| s | s.timer_id != id);
+            d.timer_tags.retain(| (timer_id, _) | *timer_id != id);
+            d.pomodoros.retain(| p | p.timer_id != id);
+            d.events.retain(| e | e.timer_id != id);
+            d.timers.retain(| t | t.id != id);
+        })
+    }
+
+    fn insert_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            let id = next_id(&mut d.last_ids.session, 0);
+            d.sessions.push(SQLSessionRow {
+                id,
+                uuid: uuid_or_new(&session.uuid),
+                updated_at: now(),
+                ..session.clone()
+            });
+            id
+        })
+    }
+
+    fn update_session(&self, session: &SQLSessionRow) -> Result<(), StorageError> {
+        self.write(| d | {
+            if let Some(s) = d.sessions.iter_mut().find(| s | s.id == session.id) {
+                if (s.timer_id, s.start, s.end) != (session.timer_id, session.start, session.end) {
+                    *s = SQLSessionRow { uuid: s.uuid.clone(), updated_at: now(), ..session.clone() };
+                }
+            }
+        })
+    }
+
+    fn merge_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            d.forget_deleted(&session.uuid);
+            if let Some(s) = d.sessions.iter_mut().find(| s | s.uuid == session.uuid) {
+                *s = SQLSessionRow { id: s.id, ..session.clone() };
+                return s.id;
+            }
+            let id = next_id(&mut d.last_ids.session, 0);
+            d.sessions.push(SQLSessionRow { id, ..session.clone() });
+            id
+        })
+    }
+
+    fn delete_session(&self, id: i64) -> Result<(), StorageError> {
+        self.write(| d | {
+            if let Some(uuid) = d.sessions.iter().find(| s | s.id == id).map(| s | s.uuid.clone()) {
+                d.record_deleted(DELETED_SESSION, uuid);
+            }
+            d.sessions.retain(| s | s.id != id);
+        })
+    }
+
+    fn get_session_by_uuid(&self, uuid: &str) -> Result<Option<SQLSessionRow>, StorageError> {
+        Ok(self.read(| d | d.sessions.iter().find(| s | s.uuid == uuid).cloned()))
+    }
+
+    fn get_deleted_uuids(&self) -> Result<Vec<SQLDeletedRow>, StorageError> {
+        let mut rows = self.read(| d | d.deleted.clone());
+        rows.sort_by(| a, b | (a.deleted_at, &a.uuid).cmp(&(b.deleted_at, &b.uuid)));
+        Ok(rows)
+    }
+
+    fn insert_deleted_uuid(&self, deleted: &SQLDeletedRow) -> Result<(), StorageError> {
+        self.write(| d | {
+            match d.deleted.iter_mut().find(| x | x.uuid == deleted.uuid) {
+                Some(x) => x.deleted_at = x.deleted_at.max(deleted.deleted_at),
+                None => d.deleted.push(deleted.clone())
+            }
+        })
+    }
+
+    fn get_sessions_by_timer(&self, timer_id: i64) -> Result<Vec<SQLSessionRow>, StorageError> {
+        let mut rows: Vec<SQLSessionRow> = self.read(| d | d.sessions.iter()
+            .filter(| s | s.timer_id == timer_id)
+            .cloned()
+            .collect());
+        rows.sort_by_key(| s | (s.start, s.id));
+        Ok(rows)
+    }
+
+    fn get_sessions_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLSessionRow>, StorageError> {
+        let ids: HashSet<i64> = timer_ids.iter().copied().collect();
+        let mut rows: Vec<SQLSessionRow> = self.read(| d | d.sessions.iter()
+            .filter(| s | ids.contains(&s.timer_id))
+            .cloned()
+            .collect());
+        rows.sort_by_key(| s | (s.timer_id, s.start, s.id));
+        Ok(rows)
+    }
+
+    fn insert_pomodoro(&self, pomodoro: &SQLPomodoroRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            let id = next_id(&mut d.last_ids.pomodoro, 0);
+            d.pomodoros.push(SQLPomodoroRow { id, ..pomodoro.clone() });
+            id
+        })
+    }
+
+    fn get_pomodoros_by_timer(&self, timer_id: i64) -> Result<Vec<SQLPomodoroRow>, StorageError> {
+        let mut rows: Vec<SQLPomodoroRow> = self.read(| d | d.pomodoros.iter()
+            .filter(| p | p.timer_id == timer_id)
+            .cloned()
+            .collect());
+        rows.sort_by_key(| p | (p.start, p.id));
+        Ok(rows)
+    }
+
+    fn get_pomodoros_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLPomodoroRow>, StorageError> {
+        let ids: HashSet<i64> = timer_ids.iter().copied().collect();
+        let mut rows: Vec<SQLPomodoroRow> = self.read(| d | d.pomodoros.iter()
+            .filter(| p | ids.contains(&p.timer_id))
+            .cloned()
+            .collect());
+        rows.sort_by_key(| p | (p.timer_id, p.start, p.id));
+        Ok(rows)
+    }
+
+    fn add_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError> {
+        self.write(| d | {
+            let item = (timer_id, tag.to_string());
+            if !d.timer_tags.contains(&item) {
+                d.timer_tags.push(item);
+            }
+        })
+    }
+
+    fn remove_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError> {
+        self.write(| d | d.timer_tags.retain(| (id, t) | !(*id == timer_id && t == tag)))
+    }
+
+    fn get_tags_by_timer(&self, timer_id: i64) -> Result<Vec<String>, StorageError> {
+        let mut tags: Vec<String> = self.read(| d | d.timer_tags.iter()
+            .filter(| (id, _) | *id == timer_id)
+            .map(| (_, tag) | tag.clone())
+            .collect());
+        tags.sort();
+        Ok(tags)
+    }
+
+    fn get_tags_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<(i64, String)>, StorageError> {
+        let ids: HashSet<i64> = timer_ids.iter().copied().collect();
+        let mut tags: Vec<(i64, String)> = self.read(| d | d.timer_tags.iter()
+            .filter(| (id, _) | ids.contains(id))
+            .cloned()
+            .collect());
+        tags.sort();
+        Ok(tags)
+    }
+
+    fn restore_snapshot(
+        &self,
+        timer_id: i64,
+        snapshot: Option<&TimerSnapshot>
+    ) -> Result<(), StorageError> {
+        self.write(| d | {
+            d.record_deleted_timers(&[timer_id]);
+            d.timers.retain(| t | t.id != timer_id);
+            d.sessions.retain(| s | s.timer_id != timer_id);
+            d.timer_tags.retain(| (id, _) | *id != timer_id);
+            let Some(snapshot) = snapshot else { return };
+            next_id(&mut d.last_ids.timer, timer_id);
+            d.forget_deleted(&snapshot.timer.uuid);
+            d.timers.push(SQLTimerRow {
+                id: timer_id,
+                uuid: uuid_or_new(&snapshot.timer.uuid),
+                updated_at: now(),
+                ..snapshot.timer.clone()
+            });
+            for session in snapshot.sessions.iter() {
+                let id = next_id(&mut d.last_ids.session, session.id);
+                d.forget_deleted(&session.uuid);
+                d.sessions.push(SQLSessionRow {
+                    id,
+                    timer_id,
+                    uuid: uuid_or_new(&session.uuid),
+                    updated_at: now(),
+                    ..session.clone()
+                });
+            }
+            for tag in snapshot.tags.iter() {
+                d.timer_tags.push((timer_id, tag.clone()));
+            }
+        })
+    }
+
+    fn insert_event(&self, event: &SQLEventRow) -> Result<i64, StorageError> {
+        self.write(| d | {
+            let id = next_id(&mut d.last_ids.event, 0);
+            d.events.push(SQLEventRow { id, ..event.clone() });
+            id
+        })
+    }
+
+    fn set_event_undone(&self, id: i64, undone: bool) -> Result<(), StorageError> {
+        self.write(| d | {
+            if let Some(e) = d.events.iter_mut().find(| e | e.id == id) {
+                e.undone = undone;
+            }
+        })
+    }
+
+    fn delete_undone_events(&self) -> Result<(), StorageError> {
+        self.write(| d | d.events.retain(| e | !e.undone))
+    }
+
+    fn group_events(&self, after_id: i64) -> Result<(), StorageError> {
+        self.write(| d | {
+            let mut events: Vec<&mut SQLEventRow> = d.events.iter_mut()
+                .filter(| e | e.id > after_id && !e.undone)
+                .collect();
+            let group_id = events.iter().map(| e | e.id).min();
+            events.iter_mut().for_each(| e | e.group_id = group_id);
+        })
+    }
+
+    fn get_next_event(&self, undone: bool) -> Result<Option<SQLEventRow>, StorageError> {
+        Ok(self.read(| d | {
+            let events = d.events.iter().filter(| e | e.undone == undone);
+            if undone {
+                events.min_by_key(| e | e.id).cloned()
+            } else {
+                events.max_by_key(| e | e.id).cloned()
+            }
+        }))
+    }
+
+    fn insert_project(&self, name: &str) -> Result<i64, StorageError> {
+        self.write(| d | {
+            let id = next_id(&mut d.last_ids.project, 0);
+            d.projects.push(SQLProjectRow { id, name: name.to_string(), archived: false });
+            id
+        })
+    }
+
+    fn update_project(&self, project: &SQLProjectRow) -> Result<(), StorageError> {
+        self.write(| d | {
+            if let Some(p) = d.projects.iter_mut().find(| p | p.id == project.id) {
+                *p = project.clone();
+            }
+        })
+    }
+
+    fn get_project_by_id(&self, id: i64) -> Result<SQLProjectRow, StorageError> {
+        self.read(| d | d.projects.iter().find(| p | p.id == id).cloned())
+            .ok_or(StorageError::ProjectDoesNotExists)
+    }
+
+    fn get_project_by_name(&self, name: &str) -> Result<SQLProjectRow, StorageError> {
+        self.read(| d | d.projects.iter().find(| p | p.name == name).cloned())
+            .ok_or(StorageError::ProjectDoesNotExists)
+    }
+
+    fn get_projects(&self, with_archived: bool) -> Result<Vec<SQLProjectRow>, StorageError> {
+        let mut rows: Vec<SQLProjectRow> = self.read(| d | d.projects.iter()
+            .filter(| p | with_archived || !p.archived)
+            .cloned()
+            .collect());
+        rows.sort_by(| a, b | a.name.cmp(&b.name));
+        Ok(rows)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(task: &str, start: u64, status: u32) -> SQLTimerRow {
+        SQLTimerRow {
+            task: task.to_string(),
+            start,
+            end: start + 60,
+            status,
+            ..SQLTimerRow::default()
+        }
+    }
+
+    #[test]
+    fn test_ids() {
+        let store = MemoryStore::new();
+        assert_eq!(store.insert_timer(&timer("a", 0, 0)).unwrap(), 1);
+        assert_eq!(store.insert_timer(&SQLTimerRow { id: 5, ..timer("b", 0, 0) }).unwrap(), 5);
+        assert_eq!(store.insert_timer(&timer("c", 0, 0)).unwrap(), 6);
+        store.restore_snapshot(6, None).unwrap();
+        assert_eq!(store.insert_timer(&timer("d", 0, 0)).unwrap(), 7);
+        assert_eq!(store.get_timer_by_id(6).err(), Some(StorageError::TimerDoesNotExists));
+    }
+
+    #[test]
+    fn test_select() {
+        let store = MemoryStore::new();
+        let day = 86400;
+        let a = store.insert_timer(&timer("a", 1735689600, 2)).unwrap();
+        let b = store.insert_timer(&timer("b", 1735689600 + day, 2)).unwrap();
+        store.insert_timer(&timer("c", 1735689600 + 2 * day, 1)).unwrap();
+        store.add_timer_tag(a, "review").unwrap();
+        store.add_timer_tag(b, "coding").unwrap();
+
+        let ids = | rows: Vec<SQLTimerRow> | rows.iter().map(| t | t.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.get_timers_by_status(2, -1).unwrap()), vec![2, 1]);
+        assert_eq!(ids(store.get_timers_by_status(2, 1).unwrap()), vec![2]);
+        assert_eq!(store.count_timers_by_status(1).unwrap(), 1);
+        assert_eq!(ids(store.get_timers_by_date(-1, &TimerFilter {
+            date_from: Some("2025-01-02".to_string()),
+            ..TimerFilter::default()
+        }).unwrap()), vec![3, 2]);
+        assert_eq!(ids(store.get_timers_by_date(-1, &TimerFilter {
+            tags_exclude: vec!["coding".to_string()],
+            ..TimerFilter::default()
+        }).unwrap()), vec![3, 1]);
+        assert_eq!(ids(store.get_timers_by_date(-1, &TimerFilter {
+            tags_include: vec!["coding".to_string(), "review".to_string()],
+            ..TimerFilter::default()
+        }).unwrap()), vec![2, 1]);
+        assert_eq!(
+            store.get_timers_by_date(-1, &TimerFilter {
+                project: Some("none".to_string()),
+                ..TimerFilter::default()
+            }).err(),
+            Some(StorageError::ProjectDoesNotExists)
+        );
+        let page = | offset, after_id | ids(store.get_timers_page(
+            &TimerFilter::default(),
+            &TimerPage { limit: 1, offset, after_id }
+        ).unwrap());
+        assert_eq!(page(1, None), vec![2]);
+        assert_eq!(page(0, Some(2)), vec![1]);
+    }
+
+    #[test]
+    fn test_search() {
+        let store = MemoryStore::new();
+        store.insert_timer(&timer("Review of the release notes", 0, 2)).unwrap();
+        store.insert_timer(&timer("code review", 0, 2)).unwrap();
+        store.insert_timer(&timer("preview", 0, 2)).unwrap();
+        let ids = | rows: Vec<SQLTimerRow> | rows.iter().map(| t | t.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.search_timers("review", -1).unwrap()), vec![2, 1]);
+        assert_eq!(ids(store.search_timers("rev rel", -1).unwrap()), vec![1]);
+        assert!(store.search_timers("  ", -1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_atomic() {
+        let store = MemoryStore::new();
+        let result: Result<(), StorageError> = store.atomic(|| {
+            store.insert_timer(&timer("a", 0, 0))?;
+            Err(StorageError::TimerDoesNotExists)
+        });
+        assert!(result.is_err());
+        assert_eq!(store.count_timers_by_status(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_json() {
+        let path = std::env::temp_dir().join(format!("focus_timer_store_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let store = MemoryStore::from_json(path.clone()).unwrap();
+        let id = store.insert_timer(&timer("a", 10, 2)).unwrap();
+        store.add_timer_tag(id, "review").unwrap();
+        store.insert_project("work").unwrap();
+        drop(store);
+
+        let store = MemoryStore::from_json(path.clone()).unwrap();
+        assert_eq!(store.get_timer_by_id(id).unwrap().task, "a");
+        assert_eq!(store.get_tags_by_timer(id).unwrap(), vec!["review"]);
+        assert_eq!(store.get_project_by_name("work").unwrap().id, 1);
+        assert_eq!(store.insert_timer(&timer("b", 20, 2)).unwrap(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+/// Version of the schema this build works with,
+/// it is the version of the last migration.
+pub const SCHEMA_VERSION: i32 = 9;
+
+/// One step of the schema upgrade, `sql` brings a database
+/// from `version - 1` to `version`.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub sql: &'static str
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: "
+            CREATE TABLE IF NOT EXISTS db_params (
+                param STRING,
+                value_int INTEGER,
+                value_str STRING,
+                value_float FLOAT
+            );
+            CREATE TABLE IF NOT EXISTS timers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                start INTEGER,
+                task STRING,
+                end INTEGER,
+                idle INTEGER,
+                status INTEGER
+            );
+        "
+    },
+    Migration {
+        version: 2,
+        description: "sessions, pomodoros, projects, tags and change journal",
+        sql: "
+            CREATE TABLE IF NOT EXISTS timer_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timer_id INTEGER,
+                start INTEGER,
+                end INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS pomodoros (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timer_id INTEGER,
+                start INTEGER,
+                end INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS projects (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name STRING UNIQUE,
+                archived INTEGER DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name STRING UNIQUE
+            );
+            CREATE TABLE IF NOT EXISTS timer_tags (
+                timer_id INTEGER,
+                tag_id INTEGER,
+                PRIMARY KEY (timer_id, tag_id)
+            );
+            CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timer_id INTEGER,
+                action STRING,
+                before STRING,
+                after STRING,
+                created_at INTEGER,
+                undone INTEGER DEFAULT 0
+            );
+            ALTER TABLE timers ADD COLUMN project_id INTEGER;
+        "
+    },
+    Migration {
+        version: 3,
+        description: "trash: deletion time and status before delete",
+        sql: "
+            ALTER TABLE timers ADD COLUMN deleted_at INTEGER;
+            ALTER TABLE timers ADD COLUMN status_before_delete INTEGER;
+        "
+    },
+    Migration {
+        version: 4,
+        description: "first completion and reopen time",
+        sql: "
+            ALTER TABLE timers ADD COLUMN completed_at INTEGER;
+            ALTER TABLE timers ADD COLUMN reopened_at INTEGER;
+        "
+    },
+    Migration {
+        version: 5,
+        description: "full-text search over task names",
+        sql: "
+            CREATE VIRTUAL TABLE timers_fts USING fts5(
+                task,
+                content = 'timers',
+                content_rowid = 'id'
+            );
+            INSERT INTO timers_fts (timers_fts) VALUES ('rebuild');
+            CREATE TRIGGER timers_fts_insert AFTER INSERT ON timers BEGIN
+                INSERT INTO timers_fts (rowid, task) VALUES (new.id, new.task);
+            END;
+            CREATE TRIGGER timers_fts_delete AFTER DELETE ON timers BEGIN
+                INSERT INTO timers_fts (timers_fts, rowid, task) VALUES ('delete', old.id, old.task);
+            END;
+            CREATE TRIGGER timers_fts_update AFTER UPDATE OF task ON timers BEGIN
+                INSERT INTO timers_fts (timers_fts, rowid, task) VALUES ('delete', old.id, old.task);
+                INSERT INTO timers_fts (rowid, task) VALUES (new.id, new.task);
+            END;
+        "
+    },
+    Migration {
+        version: 6,
+        description: "uuid and change time of timers and sessions for sync",
+        sql: "
+            ALTER TABLE timers ADD COLUMN uuid STRING;
+            ALTER TABLE timers ADD COLUMN updated_at INTEGER;
+            ALTER TABLE timer_sessions ADD COLUMN uuid STRING;
+            ALTER TABLE timer_sessions ADD COLUMN updated_at INTEGER;
+            UPDATE timers SET
+                uuid = lower(
+                    hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                    substr(hex(randomblob(2)), 2) || '-' ||
+                    substr('89AB', 1 + abs(random()) % 4, 1) ||
+                    substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))
+                ),
+                updated_at = MAX(start, end);
+            UPDATE timer_sessions SET
+                uuid = lower(
+                    hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                    substr(hex(randomblob(2)), 2) || '-' ||
+                    substr('89AB', 1 + abs(random()) % 4, 1) ||
+                    substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))
+                ),
+                updated_at = COALESCE(end, start);
+            CREATE UNIQUE INDEX timers_uuid ON timers (uuid);
+            CREATE UNIQUE INDEX timer_sessions_uuid ON timer_sessions (uuid);
+        "
+    },
+    Migration {
+        version: 7,
+        description: "indexes for listing and loading timers",
+        sql: "
+            CREATE INDEX timers_start ON timers (start);
+            CREATE INDEX timers_end ON timers (end);
+            CREATE INDEX timers_status ON timers (status);
+            CREATE INDEX timers_project ON timers (project_id, start);
+            CREATE INDEX timer_sessions_timer ON timer_sessions (timer_id, start);
+            CREATE INDEX pomodoros_timer ON pomodoros (timer_id);
+            CREATE INDEX timer_tags_tag ON timer_tags (tag_id);
+            CREATE INDEX events_timer ON events (timer_id);
+        "
+    },
+    Migration {
+        version: 8,
+        description: "undo groups for commands changing several timers",
+        sql: "
+            ALTER TABLE events ADD COLUMN group_id INTEGER;
+        "
+    },
+    Migration {
+        version: 9,
+        description: "uuids of removed timers and sessions for sync",
+        sql: "
+            CREATE TABLE deleted_uuids (
+                uuid STRING PRIMARY KEY,
+                kind STRING,
+                deleted_at INTEGER
+            );
+        "
+    }
+];
+
+/// Migrations a database of version `from` still needs, in order.
+pub fn pending(from: i32) -> Vec<&'static Migration> {
+    MIGRATIONS.iter().filter(| m | m.version > from).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_ordered() {
+        for (n, m) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(m.version, n as i32 + 1);
+        }
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending(0).len(), MIGRATIONS.len());
+        assert_eq!(pending(2)[0].version, 3);
+        assert!(pending(SCHEMA_VERSION).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::time::Duration;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Work(Duration),
+    ShortBreak(Duration),
+    LongBreak(Duration)
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase::Work(d) => write!(f, "Work {} min", d.as_secs() / 60),
+            Phase::ShortBreak(d) => write!(f, "Short break {} min", d.as_secs() / 60),
+            Phase::LongBreak(d) => write!(f, "Long break {} min", d.as_secs() / 60)
+        }
+    }
+}
+
+impl Phase {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Phase::Work(d) | Phase::ShortBreak(d) | Phase::LongBreak(d) => *d
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PomodoroConfig {
+    pub work: Duration,
+    pub short_break: Duration,
+    pub long_break: Duration,
+    pub long_break_every: u32,
+    pub cycles: u32
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work: Duration::from_secs(25 * 60),
+            short_break: Duration::from_secs(5 * 60),
+            long_break: Duration::from_secs(15 * 60),
+            long_break_every: 4,
+            cycles: 4
+        }
+    }
+}
+
+impl PomodoroConfig {
+
+    /// Work intervals interleaved with breaks. Every `long_break_every`-th
+    /// break is a long one, there is no break after the last work interval.
+    pub fn phases(&self) -> Vec<Phase> {
+        let mut phases = Vec::new();
+        for n in 1..=self.cycles {
+            phases.push(Phase::Work(self.work));
+            if n == self.cycles {
+                break;
+            }
+            if self.long_break_every > 0 && n % self.long_break_every == 0 {
+                phases.push(Phase::LongBreak(self.long_break));
+            } else {
+                phases.push(Phase::ShortBreak(self.short_break));
+            }
+        }
+        phases
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases() {
+        let config = PomodoroConfig {
+            cycles: 5,
+            long_break_every: 2,
+            ..PomodoroConfig::default()
+        };
+        let phases = config.phases();
+        assert_eq!(phases.len(), 9);
+        assert_eq!(phases[1], Phase::ShortBreak(config.short_break));
+        assert_eq!(phases[3], Phase::LongBreak(config.long_break));
+        assert_eq!(phases[8], Phase::Work(config.work));
+    }
+
+    #[test]
+    fn test_phases_empty() {
+        let config = PomodoroConfig { cycles: 0, ..PomodoroConfig::default() };
+        assert!(config.phases().is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, PartialEq)]
+pub enum ProfileError {
+    ProfileDoesNotExist(String),
+    ProfileExists(String),
+    WrongName(String),
+    ProfileIsActive(String),
+    DefaultProfile
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::ProfileDoesNotExist(name) => write!(
+                f, "Profile {name} does not exist, create it with `profile create {name}`"
+            ),
+            ProfileError::ProfileExists(name) => write!(f, "Profile {name} already exists"),
+            ProfileError::WrongName(name) => write!(
+                f, "Wrong profile name {name}, use letters, digits, - and _"
+            ),
+            ProfileError::ProfileIsActive(name) => write!(
+                f, "Profile {name} is active, switch to another one first"
+            ),
+            ProfileError::DefaultProfile => write!(f, "Default profile cannot be removed")
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+/// Named databases in the app directory. The default profile uses
+/// `<dir>/database.db` as before profiles, the others
+/// `<dir>/profiles/<name>/database.db`. The active one is kept in `<dir>/profile`.
+#[derive(Debug)]
+pub struct Profiles {
+    dir: PathBuf
+}
+
+impl Profiles {
+
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn profile_dir(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            self.dir.clone()
+        } else {
+            self.dir.join("profiles").join(name)
+        }
+    }
+
+    pub fn db_path(&self, name: &str) -> PathBuf {
+        self.profile_dir(name).join("database.db")
+    }
+
+    /// Names are letters, digits, `-` and `_` so that a profile
+    /// never points outside of `<dir>/profiles`.
+    pub fn validate_name(name: &str) -> Result<(), ProfileError> {
+        let valid = !name.is_empty()
+            && name.chars().all(| c | c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ProfileError::WrongName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        if Self::validate_name(name).is_err() {
+            return false;
+        }
+        name == DEFAULT_PROFILE || self.profile_dir(name).is_dir()
+    }
+
+    /// Database of an existing profile.
+    pub fn existing_db_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        Self::validate_name(name)?;
+        if !self.exists(name) {
+            return Err(ProfileError::ProfileDoesNotExist(name.to_string()));
+        }
+        Ok(self.db_path(name))
+    }
+
+    /// The profile chosen with `use`, the default one if it was removed.
+    pub fn active(&self) -> String {
+        match fs::read_to_string(self.dir.join("profile")) {
+            Ok(name) if self.exists(name.trim()) => name.trim().to_string(),
+            _ => DEFAULT_PROFILE.to_string()
+        }
+    }
+
+    /// All profiles by name, the default one first.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = match fs::read_dir(self.dir.join("profiles")) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter(| e | e.path().is_dir())
+                .map(| e | e.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e)
+        };
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+        Ok(names)
+    }
+
+    /// Creates the directory of a new profile, its database
+    /// is created when it is opened first.
+    pub fn create(&self, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+        Self::validate_name(name)?;
+        if self.exists(name) {
+            return Err(Box::new(ProfileError::ProfileExists(name.to_string())));
+        }
+        fs::create_dir_all(self.profile_dir(name))?;
+        Ok(self.db_path(name))
+    }
+
+    pub fn use_profile(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        Self::validate_name(name)?;
+        self.existing_db_path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("profile"), name)?;
+        Ok(())
+    }
+
+    /// Removes a profile with its database and backups.
+    pub fn remove(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        Self::validate_name(name)?;
+        if name == DEFAULT_PROFILE {
+            return Err(Box::new(ProfileError::DefaultProfile));
+        }
+        self.existing_db_path(name)?;
+        if self.active() == name {
+            return Err(Box::new(ProfileError::ProfileIsActive(name.to_string())));
+        }
+        let profiles_dir = self.dir.join("profiles").canonicalize()?;
+        let dir = self.profile_dir(name).canonicalize()?;
+        if dir.parent() != Some(profiles_dir.as_path()) {
+            return Err(Box::new(ProfileError::WrongName(name.to_string())));
+        }
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_profiles_{}", std::process::id()));
+        let profiles = Profiles::new(dir.clone());
+        assert_eq!(profiles.active(), DEFAULT_PROFILE);
+        assert_eq!(profiles.list().unwrap(), vec![DEFAULT_PROFILE]);
+        assert_eq!(profiles.db_path(DEFAULT_PROFILE), dir.join("database.db"));
+
+        let path = profiles.create("work").unwrap();
+        assert_eq!(path, dir.join("profiles").join("work").join("database.db"));
+        assert!(profiles.create("work").is_err());
+        assert!(profiles.create("../etc").is_err());
+        assert!(profiles.use_profile("home").is_err());
+        profiles.create("home").unwrap();
+        profiles.use_profile("work").unwrap();
+        assert_eq!(profiles.active(), "work");
+        assert_eq!(profiles.list().unwrap(), vec![DEFAULT_PROFILE, "home", "work"]);
+
+        assert!(profiles.remove("work").is_err());
+        assert!(profiles.remove(DEFAULT_PROFILE).is_err());
+        profiles.remove("home").unwrap();
+        assert!(profiles.existing_db_path("home").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_names_outside_profiles() {
+        let dir = std::env::temp_dir().join(format!("focus_timer_profiles_escape_{}", std::process::id()));
+        let profiles = Profiles::new(dir.clone());
+        profiles.create("work").unwrap();
+        fs::write(profiles.db_path(DEFAULT_PROFILE), "data").unwrap();
+        fs::create_dir_all(dir.join("x")).unwrap();
+
+        for name in ["..", "../x", ".", "", "work/..", "../../x"] {
+            assert!(!profiles.exists(name));
+            assert_eq!(
+                profiles.existing_db_path(name),
+                Err(ProfileError::WrongName(name.to_string()))
+            );
+            assert!(profiles.use_profile(name).is_err());
+            assert!(profiles.remove(name).is_err());
+        }
+        assert!(profiles.db_path(DEFAULT_PROFILE).is_file());
+        assert!(dir.join("x").is_dir());
+        assert!(profiles.exists("work"));
+        assert_eq!(profiles.active(), DEFAULT_PROFILE);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,21 +1,43 @@
-use rusqlite;
-use rusqlite::{Connection, Row};
+use rusqlite::{Connection, DatabaseName, OpenFlags, Row, ToSql, Transaction, TransactionBehavior};
 use std::fmt;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use chrono::{NaiveDateTime, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::migrations::{self, Migration, SCHEMA_VERSION};
+use crate::store::{TimerStore, now, uuid_or_new};
 
 
-const SCHEMA_VERSION: i32 = 1;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
-#[derive(Debug)]
+const TIMER_FIELDS: &str = "
+    id, task, start, end, idle, status, project_id, deleted_at, status_before_delete,
+    completed_at, reopened_at, uuid, updated_at
+";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SQLTimerRow {
     pub id: i64,
     pub task: String,
     pub start: u64,
     pub end: u64,
     pub idle: i64,
-    pub status: u32
+    pub status: u32,
+    pub project_id: Option<i64>,
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+    #[serde(default)]
+    pub status_before_delete: Option<u32>,
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+    #[serde(default)]
+    pub reopened_at: Option<u64>,
+    /// Identifies the timer across databases, see `sync`.
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(default)]
+    pub updated_at: u64
 }
 
 impl SQLTimerRow {
@@ -26,28 +48,282 @@ impl SQLTimerRow {
             start: row.get("start")?,
             end: row.get("end")?,
             idle: row.get("idle")?,
-            status: row.get("status")?
+            status: row.get("status")?,
+            project_id: row.get("project_id")?,
+            deleted_at: row.get("deleted_at")?,
+            status_before_delete: row.get("status_before_delete")?,
+            completed_at: row.get("completed_at")?,
+            reopened_at: row.get("reopened_at")?,
+            uuid: row.get::<_, Option<String>>("uuid")?.unwrap_or_default(),
+            updated_at: row.get::<_, Option<u64>>("updated_at")?.unwrap_or_default()
+        })
+    }
+}
+
+/// Full state of a timer as stored in the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimerSnapshot {
+    pub timer: SQLTimerRow,
+    pub sessions: Vec<SQLSessionRow>,
+    pub tags: Vec<String>
+}
+
+/// A change of a timer, `before` is `None` for a created timer
+/// and `after` is `None` for a removed one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SQLEventRow {
+    pub id: i64,
+    pub timer_id: i64,
+    pub action: String,
+    pub before: Option<TimerSnapshot>,
+    pub after: Option<TimerSnapshot>,
+    pub created_at: u64,
+    pub undone: bool,
+    /// Events of one command share the id of the first of them
+    /// and are undone and redone together.
+    #[serde(default)]
+    pub group_id: Option<i64>
+}
+
+/// Turns words typed by the user into an FTS5 query matching rows
+/// which have all of them as word prefixes, quotes keep FTS5 syntax
+/// characters from being interpreted.
+fn fts_query(query: &str) -> String {
+    query.split_whitespace()
+        .map(| word | format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Timer ids as a JSON array, `json_each` turns it into rows so a list
+/// of any length is passed as one parameter.
+fn id_list(ids: &[i64]) -> String {
+    let ids: Vec<String> = ids.iter().map(| id | id.to_string()).collect();
+    format!("[{}]", ids.join(","))
+}
+
+fn snapshot_from_sql(value: Option<String>) -> rusqlite::Result<Option<TimerSnapshot>> {
+    match value {
+        Some(s) => serde_json::from_str(&s)
+            .map(Some)
+            .map_err(| e | rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                Box::new(e)
+            )),
+        None => Ok(None)
+    }
+}
+
+fn snapshot_to_sql(value: &Option<TimerSnapshot>) -> Result<Option<String>, StorageError> {
+    match value {
+        Some(snapshot) => serde_json::to_string(snapshot)
+            .map(Some)
+            .map_err(| e | StorageError::DatabaseError(
+                rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+            )),
+        None => Ok(None)
+    }
+}
+
+impl SQLEventRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            timer_id: row.get("timer_id")?,
+            action: row.get("action")?,
+            before: snapshot_from_sql(row.get("before")?)?,
+            after: snapshot_from_sql(row.get("after")?)?,
+            created_at: row.get("created_at")?,
+            undone: row.get("undone")?,
+            group_id: row.get("group_id")?
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SQLProjectRow {
+    pub id: i64,
+    pub name: String,
+    pub archived: bool
+}
+
+impl SQLProjectRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            archived: row.get("archived")?
+        })
+    }
+}
+
+/// Conditions for selecting timers, `None` or empty means no restriction.
+/// A timer matches `tags_include` if it has any of these tags and
+/// `tags_exclude` if it has none of them.
+#[derive(Debug, Default, Clone)]
+pub struct TimerFilter {
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub project: Option<String>,
+    pub tags_include: Vec<String>,
+    pub tags_exclude: Vec<String>
+}
+
+/// Part of a listing: at most `limit` timers (all of them if it is negative)
+/// after skipping `offset` ones or, with `after_id`, the ones listed after that timer.
+/// A cursor keeps pages stable while timers are added and is fast at any depth.
+#[derive(Debug, Clone, Copy)]
+pub struct TimerPage {
+    pub limit: i32,
+    pub offset: u64,
+    pub after_id: Option<i64>
+}
+
+impl Default for TimerPage {
+    fn default() -> Self {
+        TimerPage { limit: -1, offset: 0, after_id: None }
+    }
+}
+
+/// Kinds of rows in `deleted_uuids`.
+pub const DELETED_TIMER: &str = "timer";
+pub const DELETED_SESSION: &str = "session";
+
+/// A timer or session removed for good. It is kept so that `sync`
+/// removes the row from the other database instead of copying it back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SQLDeletedRow {
+    pub uuid: String,
+    pub kind: String,
+    pub deleted_at: u64
+}
+
+impl SQLDeletedRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            uuid: row.get("uuid")?,
+            kind: row.get("kind")?,
+            deleted_at: row.get("deleted_at")?
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SQLSessionRow {
+    pub id: i64,
+    pub timer_id: i64,
+    pub start: u64,
+    pub end: Option<u64>,
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(default)]
+    pub updated_at: u64
+}
+
+impl SQLSessionRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            timer_id: row.get("timer_id")?,
+            start: row.get("start")?,
+            end: row.get("end")?,
+            uuid: row.get::<_, Option<String>>("uuid")?.unwrap_or_default(),
+            updated_at: row.get::<_, Option<u64>>("updated_at")?.unwrap_or_default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SQLPomodoroRow {
+    pub id: i64,
+    pub timer_id: i64,
+    pub start: u64,
+    pub end: u64
+}
+
+impl SQLPomodoroRow {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            timer_id: row.get("timer_id")?,
+            start: row.get("start")?,
+            end: row.get("end")?
         })
     }
 }
 
+/// Something wrong found by `Storage::find_problems`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbProblem {
+    Corrupted(String),
+    SearchIndexBroken,
+    UnknownStatus(i64, i64),
+    UnknownStatusBeforeDelete(i64, i64),
+    EndBeforeStart(i64),
+    NegativeIdle(i64),
+    IdleExceedsSpan(i64),
+    ExtraRunning(i64)
+}
+
+impl fmt::Display for DbProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbProblem::Corrupted(e) => write!(f, "Database is corrupted: {e}"),
+            DbProblem::SearchIndexBroken => write!(f, "Search index does not match tasks"),
+            DbProblem::UnknownStatus(id, status) => write!(f, "Task {id}: unknown status {status}"),
+            DbProblem::UnknownStatusBeforeDelete(id, status) => write!(
+                f, "Task {id}: unknown status before deletion {status}"
+            ),
+            DbProblem::EndBeforeStart(id) => write!(f, "Task {id}: end is before start"),
+            DbProblem::NegativeIdle(id) => write!(f, "Task {id}: idle time is negative"),
+            DbProblem::IdleExceedsSpan(id) => write!(f, "Task {id}: idle time is longer than the task"),
+            DbProblem::ExtraRunning(id) => write!(f, "Task {id}: another task is running too")
+        }
+    }
+}
+
+impl DbProblem {
+    /// What `--fix` does about the problem, `None` if it cannot be fixed.
+    pub fn repair(&self) -> Option<&'static str> {
+        match self {
+            DbProblem::Corrupted(_) => None,
+            DbProblem::SearchIndexBroken => Some("rebuild the index"),
+            DbProblem::UnknownStatus(..) => Some("set status to PAUSED"),
+            DbProblem::UnknownStatusBeforeDelete(..) => Some("set status before deletion to PAUSED"),
+            DbProblem::EndBeforeStart(_) => Some("set end to start"),
+            DbProblem::NegativeIdle(_) => Some("set idle to 0"),
+            DbProblem::IdleExceedsSpan(_) => Some("clamp idle to the task span"),
+            DbProblem::ExtraRunning(_) => Some("pause the task")
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum StorageError {
     DatabaseError(rusqlite::Error),
     SchemaVersionError,
     TimerDoesNotExists,
+    ProjectDoesNotExists,
     ConnectionNotFound,
-    WrongDatetimeFormat
+    WrongDatetimeFormat,
+    FileError(String),
+    UnknownStatus(i64, u32)
 }
 
 impl fmt::Display for StorageError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             StorageError::TimerDoesNotExists => write!(f, "Timer does not exist"),
-            StorageError::SchemaVersionError => write!(f, "Version of db is no correct"),
+            StorageError::ProjectDoesNotExists => write!(f, "Project does not exist"),
+            StorageError::SchemaVersionError => write!(f, "Database is newer than this version of the app"),
             StorageError::ConnectionNotFound => write!(f, "Connection to storage is not found"),
             StorageError::DatabaseError(e) => write!(f, "DatabaseError: {e}"),
-            StorageError::WrongDatetimeFormat => write!(f, "Wrong date time format")
+            StorageError::WrongDatetimeFormat => write!(f, "Wrong date time format"),
+            StorageError::FileError(e) => write!(f, "FileError: {e}"),
+            StorageError::UnknownStatus(id, status) => write!(
+                f, "Task {id} has unknown status {status}, repair it with `db check --fix`"
+            )
         }
     }
 }
@@ -63,12 +339,21 @@ impl From<rusqlite::Error> for StorageError {
 
 #[derive(Debug)]
 pub struct Storage {
-    conn: Connection
+    conn: Connection,
+    path: Option<PathBuf>
 }
 
 impl Storage {
 
     fn get_version(&self) -> Result<Option<i32>, StorageError> {
+        let has_params: bool = self.conn.query_row(
+            "SELECT count() > 0 FROM sqlite_master WHERE type = 'table' AND name = 'db_params'",
+            [],
+            | row | row.get(0)
+        )?;
+        if !has_params {
+            return Ok(None);
+        }
         match self.conn.query_row(
             "SELECT value_int FROM db_params WHERE param == 'version'",
             [],
@@ -113,49 +398,296 @@ impl Storage {
         Err(StorageError::WrongDatetimeFormat)
     }
 
+    /// Opens the database and brings its schema up to date.
     pub fn new(path: Option<PathBuf>) -> Result<Self, StorageError> {
-        let storage = Storage {
-            conn: if let Some(path) = path {
-                Connection::open(path)?
-            } else {
-                Connection::open_in_memory()?
+        let storage = match path {
+            Some(path) => {
+                let storage = Self::open(path)?;
+                storage.conn.pragma_update(None, "journal_mode", "WAL")?;
+                storage
+            },
+            None => Self::from_connection(Connection::open_in_memory()?, None)
+        };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    fn from_connection(conn: Connection, path: Option<PathBuf>) -> Self {
+        Storage { conn, path }
+    }
+
+    /// Opens a database without upgrading its schema,
+    /// see `pending_migrations` and `migrate`.
+    /// A locked database is waited for up to `BUSY_TIMEOUT`.
+    pub fn open(path: PathBuf) -> Result<Self, StorageError> {
+        let conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(Self::from_connection(conn, Some(path)))
+    }
+
+    /// Opens an existing database for reading only,
+    /// neither the file nor its schema is changed.
+    pub fn open_read_only(path: PathBuf) -> Result<Self, StorageError> {
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(Self::from_connection(conn, Some(path)))
+    }
+
+    pub fn version(&self) -> Result<i32, StorageError> {
+        Ok(self.get_version()?.unwrap_or(0))
+    }
+
+    fn set_version(&self, version: i32) -> Result<(), StorageError> {
+        self.set_param("version", "value_int", version)
+    }
+
+    fn get_param<T: rusqlite::types::FromSql>(
+        &self,
+        param: &str,
+        column: &str
+    ) -> Result<Option<T>, StorageError> {
+        match self.conn.query_row(
+            &format!("SELECT {column} FROM db_params WHERE param == ?1"),
+            rusqlite::params![param],
+            | row | row.get(0)
+        ) {
+            Ok(value) => Ok(value),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn set_param(&self, param: &str, column: &str, value: impl ToSql) -> Result<(), StorageError> {
+        let n = self.conn.execute(
+            &format!("UPDATE db_params SET {column} = ?2 WHERE param == ?1"),
+            rusqlite::params![param, value]
+        )?;
+        if n == 0 {
+            self.conn.execute(
+                &format!("INSERT INTO db_params (param, {column}) VALUES (?1, ?2)"),
+                rusqlite::params![param, value]
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Text setting kept in `db_params`.
+    pub fn param_str(&self, param: &str) -> Result<Option<String>, StorageError> {
+        self.get_param(param, "value_str")
+    }
+
+    /// Sets a text setting, `None` clears it.
+    pub fn set_param_str(&self, param: &str, value: Option<&str>) -> Result<(), StorageError> {
+        self.set_param(param, "value_str", value)
+    }
+
+    pub fn param_int(&self, param: &str) -> Result<Option<i64>, StorageError> {
+        self.get_param(param, "value_int")
+    }
+
+    pub fn set_param_int(&self, param: &str, value: Option<i64>) -> Result<(), StorageError> {
+        self.set_param(param, "value_int", value)
+    }
+
+    /// Size of the database in bytes.
+    pub fn size(&self) -> Result<u64, StorageError> {
+        Ok(self.conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            | row | row.get(0)
+        )?)
+    }
+
+    /// Records the uuids `select` returns as deleted rows of `kind`.
+    fn record_deleted(&self, kind: &str, select: &str, params: &[&dyn ToSql]) -> Result<(), StorageError> {
+        self.conn.execute(&format!("
+            INSERT OR REPLACE INTO deleted_uuids (uuid, kind, deleted_at)
+            SELECT uuid, '{kind}', {} FROM ({select}) WHERE uuid != ''
+        ", now()), params)?;
+        Ok(())
+    }
+
+    /// Records the timers `ids` selects and their sessions as deleted.
+    fn record_deleted_timers(&self, ids: &str, params: &[&dyn ToSql]) -> Result<(), StorageError> {
+        self.record_deleted(
+            DELETED_SESSION,
+            &format!("SELECT uuid FROM timer_sessions WHERE timer_id IN ({ids})"),
+            params
+        )?;
+        self.record_deleted(DELETED_TIMER, &format!("SELECT uuid FROM timers WHERE id IN ({ids})"), params)
+    }
+
+    /// Forgets the deletion of a row stored again.
+    fn forget_deleted(&self, uuid: &str) -> Result<(), StorageError> {
+        self.conn.execute("DELETE FROM deleted_uuids WHERE uuid = ?1", rusqlite::params![uuid])?;
+        Ok(())
+    }
+
+    /// Rebuilds the database file without free pages, the WAL file is truncated after.
+    pub fn vacuum(&self) -> Result<(), StorageError> {
+        self.conn.execute_batch("VACUUM")?;
+        if self.path.is_some() {
+            self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], | _ | Ok(()))?;
+        }
+        Ok(())
+    }
+
+    pub fn pending_migrations(&self) -> Result<Vec<&'static Migration>, StorageError> {
+        let version = self.version()?;
+        if version > SCHEMA_VERSION {
+            return Err(StorageError::SchemaVersionError);
+        }
+        Ok(migrations::pending(version))
+    }
+
+    /// File of the database, `None` for one kept in memory.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Copies the database into `target` with the online backup API,
+    /// it is safe to do while the database is used.
+    pub fn backup_to(&self, target: &Path) -> Result<(), StorageError> {
+        self.conn.backup(DatabaseName::Main, target, None)?;
+        Ok(())
+    }
+
+    /// Replaces the content of the database with a copy of `source`.
+    pub fn restore_from(&mut self, source: &Path) -> Result<(), StorageError> {
+        self.conn.restore(DatabaseName::Main, source, None::<fn(rusqlite::backup::Progress)>)?;
+        Ok(())
+    }
+
+    /// Runs SQLite integrity checks and looks for timers the app cannot
+    /// handle. Extra running timers come last: pausing them goes through
+    /// the timer logic, which needs the other problems fixed first.
+    pub fn find_problems(&self) -> Result<Vec<DbProblem>, StorageError> {
+        let mut problems = Vec::new();
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let messages = stmt.query_map([], | r | r.get::<_, String>(0))?;
+        for message in messages {
+            let message = message?;
+            if message != "ok" {
+                problems.push(DbProblem::Corrupted(message));
             }
+        }
+        if let Err(e) = self.conn.execute(
+            "INSERT INTO timers_fts (timers_fts, rank) VALUES ('integrity-check', 1)",
+            []
+        ) {
+            match e.sqlite_error_code() {
+                Some(rusqlite::ErrorCode::DatabaseCorrupt) => problems.push(DbProblem::SearchIndexBroken),
+                _ => return Err(e.into())
+            }
+        }
+        let ids = | q: &str | -> Result<Vec<(i64, i64)>, StorageError> {
+            let mut stmt = self.conn.prepare(q)?;
+            let items = stmt.query_map([], | r | Ok((r.get(0)?, r.get(1)?)))?;
+            Ok(items.collect::<Result<_, _>>()?)
         };
+        for (id, status) in ids("SELECT id, status FROM timers WHERE status NOT IN (0, 1, 2, 3, 9) ORDER BY id")? {
+            problems.push(DbProblem::UnknownStatus(id, status));
+        }
+        for (id, status) in ids("
+            SELECT id, status_before_delete FROM timers
+            WHERE status_before_delete IS NOT NULL AND status_before_delete NOT IN (0, 1, 2, 3)
+            ORDER BY id
+        ")? {
+            problems.push(DbProblem::UnknownStatusBeforeDelete(id, status));
+        }
+        for (id, _) in ids("SELECT id, status FROM timers WHERE end < start")? {
+            problems.push(DbProblem::EndBeforeStart(id));
+        }
+        for (id, _) in ids("SELECT id, status FROM timers WHERE idle < 0")? {
+            problems.push(DbProblem::NegativeIdle(id));
+        }
+        for (id, _) in ids("SELECT id, status FROM timers WHERE idle > MAX(end - start, 0)")? {
+            problems.push(DbProblem::IdleExceedsSpan(id));
+        }
+        for (id, _) in ids("SELECT id, status FROM timers WHERE status = 1 ORDER BY start DESC, id DESC")?
+            .into_iter()
+            .skip(1) {
+            problems.push(DbProblem::ExtraRunning(id));
+        }
+        Ok(problems)
+    }
 
-        storage.conn.execute("CREATE TABLE IF NOT EXISTS db_params (
-            param STRING,
-            value_int INTEGER,
-            value_str STRING,
-            value_float FLOAT
-        )", [])?;
-       
-        storage.conn.execute("CREATE TABLE IF NOT EXISTS timers (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            start INTEGER,
-            task STRING,
-            end INTEGER,
-            idle INTEGER,
-            status INTEGER
-        )", [])?;
-
-        match storage.get_version()? {
-            Some(ver) => {
-                if ver != SCHEMA_VERSION {
-                    return Err(StorageError::SchemaVersionError);
-                }
+    /// Repairs a problem which can be fixed with SQL alone,
+    /// returns `false` for the other ones.
+    pub fn fix_problem(&self, problem: &DbProblem) -> Result<bool, StorageError> {
+        let (q, id) = match problem {
+            DbProblem::SearchIndexBroken => {
+                self.conn.execute("INSERT INTO timers_fts (timers_fts) VALUES ('rebuild')", [])?;
+                return Ok(true);
             },
-            None => {
-                storage.conn.execute(
-                    "INSERT INTO db_params (param, value_int) VALUES (?1, ?2)",
-                    rusqlite::params!["version", SCHEMA_VERSION]
-                )?;
-            }
+            DbProblem::UnknownStatus(id, _) => ("UPDATE timers SET status = 2 WHERE id = ?1", id),
+            DbProblem::UnknownStatusBeforeDelete(id, _) => (
+                "UPDATE timers SET status_before_delete = 2 WHERE id = ?1",
+                id
+            ),
+            DbProblem::EndBeforeStart(id) => ("UPDATE timers SET end = start WHERE id = ?1", id),
+            DbProblem::NegativeIdle(id) => ("UPDATE timers SET idle = 0 WHERE id = ?1", id),
+            DbProblem::IdleExceedsSpan(id) => (
+                "UPDATE timers SET idle = MAX(end - start, 0) WHERE id = ?1",
+                id
+            ),
+            DbProblem::Corrupted(_) | DbProblem::ExtraRunning(_) => return Ok(false)
+        };
+        self.conn.execute(q, rusqlite::params![id])?;
+        Ok(true)
+    }
+
+    /// Applies pending migrations, each one in its own transaction.
+    /// A file database holding data is copied to `<name>.v<version>-<time>.bak`
+    /// first, the path of the copy is returned.
+    pub fn migrate(&self) -> Result<Option<PathBuf>, StorageError> {
+        let pending = self.pending_migrations()?;
+        if pending.is_empty() {
+            return Ok(None);
+        }
+        let version = self.version()?;
+        let backup = match &self.path {
+            Some(path) if version > 0 => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".v{}-{}.bak", version, Utc::now().format("%Y%m%d%H%M%S")));
+                let backup = PathBuf::from(name);
+                self.backup_to(&backup)?;
+                Some(backup)
+            },
+            _ => None
+        };
+        for m in pending {
+            self.atomic(|| {
+                // another process may have applied it since the check above
+                if self.version()? >= m.version {
+                    return Ok(());
+                }
+                self.conn.execute_batch(m.sql)?;
+                self.set_version(m.version)
+            })?;
         }
+        Ok(backup)
+    }
+}
 
-        Ok(storage)
+impl TimerStore for Storage {
+
+    /// Inside another `atomic` call `f` just joins the outer transaction.
+    fn atomic<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<StorageError> {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)
+            .map_err(StorageError::from)?;
+        let result = f()?;
+        tx.commit().map_err(StorageError::from)?;
+        Ok(result)
     }
 
-    pub fn is_timer_exist(&self, id: i64) -> Result<bool, StorageError> {
+    fn is_timer_exist(&self, id: i64) -> Result<bool, StorageError> {
         match self.conn.query_row(
             "SELECT count(0) AS n FROM timers WHERE id = ?1",
             rusqlite::params![id],
@@ -167,28 +699,40 @@ impl Storage {
         }
     }
 
-    pub fn insert_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError> {
+    fn insert_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError> {
         self.conn.execute("
             INSERT INTO timers
-                (task, start, end, idle, status)
-                VALUES (?1, ?2, ?3, ?4, ?5)
+                (id, task, start, end, idle, status, project_id,
+                 deleted_at, status_before_delete, completed_at, reopened_at,
+                 uuid, updated_at)
+                VALUES (NULLIF(?1, 0), ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
             ",
             rusqlite::params![
+                timer.id,
                 timer.task,
                 timer.start,
                 timer.end,
                 timer.idle,
-                timer.status
+                timer.status,
+                timer.project_id,
+                timer.deleted_at,
+                timer.status_before_delete,
+                timer.completed_at,
+                timer.reopened_at,
+                uuid_or_new(&timer.uuid),
+                now()
             ]
         )?;
         Ok(self.conn.last_insert_rowid())    
     }
 
-    pub fn update_timer(&self, timer: &SQLTimerRow) -> Result<(), StorageError> {
+    fn update_timer(&self, timer: &SQLTimerRow) -> Result<(), StorageError> {
         self.conn.execute("
             UPDATE timers SET
-                task=?1, start=?2, end=?3, idle=?4, status=?5
-            WHERE id=?6
+                task=?1, start=?2, end=?3, idle=?4, status=?5, project_id=?6,
+                deleted_at=?7, status_before_delete=?8, completed_at=?9, reopened_at=?10,
+                updated_at=?12
+            WHERE id=?11
             ",
             rusqlite::params![
                 timer.task,
@@ -196,26 +740,81 @@ impl Storage {
                 timer.end,
                 timer.idle,
                 timer.status,
-                timer.id
+                timer.project_id,
+                timer.deleted_at,
+                timer.status_before_delete,
+                timer.completed_at,
+                timer.reopened_at,
+                timer.id,
+                now()
             ]
         )?;
         Ok(())    
     }
 
-    pub fn get_timer_by_id(&self, id: i64) -> Result<SQLTimerRow, StorageError> {
-        let q = "
-            SELECT id, task, start, end, idle, status
+    fn merge_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError> {
+        let id = self.conn.query_row("
+            INSERT INTO timers
+                (task, start, end, idle, status, project_id,
+                 deleted_at, status_before_delete, completed_at, reopened_at,
+                 uuid, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+            ON CONFLICT (uuid) DO UPDATE SET
+                task=excluded.task, start=excluded.start, end=excluded.end,
+                idle=excluded.idle, status=excluded.status, project_id=excluded.project_id,
+                deleted_at=excluded.deleted_at,
+                status_before_delete=excluded.status_before_delete,
+                completed_at=excluded.completed_at, reopened_at=excluded.reopened_at,
+                updated_at=excluded.updated_at
+            RETURNING id
+            ",
+            rusqlite::params![
+                timer.task,
+                timer.start,
+                timer.end,
+                timer.idle,
+                timer.status,
+                timer.project_id,
+                timer.deleted_at,
+                timer.status_before_delete,
+                timer.completed_at,
+                timer.reopened_at,
+                timer.uuid,
+                timer.updated_at
+            ],
+            | r | r.get(0)
+        )?;
+        self.forget_deleted(&timer.uuid)?;
+        Ok(id)
+    }
+
+    fn get_timer_by_uuid(&self, uuid: &str) -> Result<SQLTimerRow, StorageError> {
+        let q = format!("
+            SELECT {TIMER_FIELDS}
+            FROM timers
+            WHERE uuid = ?1
+        ");
+        match self.conn.query_row(&q, rusqlite::params![uuid], SQLTimerRow::from_row) {
+            Ok(t) => Ok(t),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(StorageError::TimerDoesNotExists),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn get_timer_by_id(&self, id: i64) -> Result<SQLTimerRow, StorageError> {
+        let q = format!("
+            SELECT {TIMER_FIELDS}
             FROM timers
             WHERE id = ?1
-        ";
-        match self.conn.query_row(q, rusqlite::params![id], | r | SQLTimerRow::from_row(r)) {
+        ");
+        match self.conn.query_row(&q, rusqlite::params![id], SQLTimerRow::from_row) {
             Ok(t) => Ok(t),
             Err(rusqlite::Error::QueryReturnedNoRows) => Err(StorageError::TimerDoesNotExists),
             Err(e) => Err(StorageError::DatabaseError(e))
         }
     }
 
-    pub fn count_timers_by_status(&self, status: u32) -> Result<u64, StorageError> {
+    fn count_timers_by_status(&self, status: u32) -> Result<u64, StorageError> {
         match self.conn.query_row("
             SELECT count() n
             FROM timers
@@ -229,106 +828,602 @@ impl Storage {
         }
     }
 
-    pub fn get_timers_by_status(
+    fn get_timers_by_status(
         &self,
         status: u32,
         limit: i32
     ) -> Result<Vec<SQLTimerRow>, StorageError> {
-        let q = "
-            SELECT id, task, start, end, idle, status
+        let q = format!("
+            SELECT {TIMER_FIELDS}
             FROM timers
             WHERE status = ?1
             ORDER BY id DESC
             LIMIT ?2
-        ";
-        let mut stmt = self.conn.prepare(q)?;
+        ");
+        let mut stmt = self.conn.prepare(&q)?;
         let items = stmt.query_map(
             rusqlite::params![status, limit],
-            | row | SQLTimerRow::from_row(row)
+            SQLTimerRow::from_row
         )?;
-        Ok(items.filter_map(Result::ok).collect())
+        Ok(items.collect::<Result<_, _>>()?)
     }
 
-    pub fn get_last_timers(&self, limit: u64) -> Result<Vec<SQLTimerRow>, StorageError> {
-        let q = "
-            SELECT id, task, start, end, idle, status
+    fn get_last_timers(&self, limit: u64) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let q = format!("
+            SELECT {TIMER_FIELDS}
             FROM timers
             ORDER BY end DESC
             LIMIT ?1
-        ";
-        let mut stmt = self.conn.prepare(q)?;
+        ");
+        let mut stmt = self.conn.prepare(&q)?;
         let items = stmt.query_map(
             rusqlite::params![limit],
-            | row | SQLTimerRow::from_row(row)
+            SQLTimerRow::from_row
         )?;
-        Ok(items.filter_map(Result::ok).collect())
+        Ok(items.collect::<Result<_, _>>()?)
     }
 
-    pub fn get_timers_by_date(
+    /// Only the conditions in use are put into the query,
+    /// `?n IS NULL OR ...` ones would keep SQLite from using indexes.
+    fn get_timers_page(
         &self,
-        limit: i32,
-        date_from: Option<String>,
-        date_to: Option<String>
+        filter: &TimerFilter,
+        page: &TimerPage
     ) -> Result<Vec<SQLTimerRow>, StorageError> {
-        let query = "
-            SELECT id, task, start, end, idle, status
-            FROM timers
-            WHERE
-                (?1 is NULL OR start >= ?1)
-                AND (?2 is NULL OR start < ?2)
-            ORDER BY start DESC
-            LIMIT ?3
-        ";
-        let mut stmt = self.conn.prepare(query)?;
-        let from_timestamp = match date_from {
-            Some(t) => Some(Self::str_to_time(t)?),
+        let from_timestamp = match &filter.date_from {
+            Some(t) => Some(Self::str_to_time(t.clone())?),
             None => None
         };
-        let to_timestamp = match date_to {
-            Some(t) => Some(Self::str_to_time(t)?),
+        let to_timestamp = match &filter.date_to {
+            Some(t) => Some(Self::str_to_time(t.clone())?),
             None => None
         };
-        let items = stmt.query_map(
-            rusqlite::params![from_timestamp, to_timestamp, limit],
-            | row | SQLTimerRow::from_row(row)
-        )?;
-        Ok(items.filter_map(Result::ok).collect())
+        let project_id = match &filter.project {
+            Some(name) => Some(self.get_project_by_name(name)?.id),
+            None => None
+        };
+        let after = match page.after_id {
+            Some(id) => Some(self.get_timer_by_id(id)?),
+            None => None
+        };
+        let mut conditions: Vec<String> = vec![String::from("TRUE")];
+        let mut params: Vec<&dyn ToSql> = vec![];
+        if let Some(from) = &from_timestamp {
+            params.push(from);
+            conditions.push(format!("start >= ?{}", params.len()));
+        }
+        if let Some(to) = &to_timestamp {
+            params.push(to);
+            conditions.push(format!("start < ?{}", params.len()));
+        }
+        if let Some(id) = &project_id {
+            params.push(id);
+            conditions.push(format!("project_id = ?{}", params.len()));
+        }
+        if let Some(after) = &after {
+            params.push(&after.start);
+            params.push(&after.id);
+            conditions.push(format!("(start, id) < (?{}, ?{})", params.len() - 1, params.len()));
+        }
+        for (tags, cond) in [
+            (&filter.tags_include, "EXISTS"),
+            (&filter.tags_exclude, "NOT EXISTS")
+        ] {
+            if tags.is_empty() {
+                continue;
+            }
+            let placeholders: Vec<String> = (params.len() + 1..=params.len() + tags.len())
+                .map(| n | format!("?{n}"))
+                .collect();
+            conditions.push(format!("
+                {cond} (
+                    SELECT 1
+                    FROM timer_tags tt
+                    JOIN tags t ON t.id = tt.tag_id
+                    WHERE tt.timer_id = timers.id AND t.name IN ({})
+                )", placeholders.join(", ")));
+            params.extend(tags.iter().map(| t | t as &dyn ToSql));
+        }
+        let n = params.len();
+        params.push(&page.limit);
+        params.push(&page.offset);
+        let query = format!("
+            SELECT {TIMER_FIELDS}
+            FROM timers
+            WHERE {}
+            ORDER BY start DESC, id DESC
+            LIMIT ?{} OFFSET ?{}
+        ", conditions.join(" AND "), n + 1, n + 2);
+        let mut stmt = self.conn.prepare(&query)?;
+        let items = stmt.query_map(params.as_slice(), SQLTimerRow::from_row)?;
+        Ok(items.collect::<Result<_, _>>()?)
     }
 
-}
+    fn search_timers(&self, query: &str, limit: i32) -> Result<Vec<SQLTimerRow>, StorageError> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        let q = format!("
+            SELECT {TIMER_FIELDS}
+            FROM timers
+            JOIN (
+                SELECT rowid AS match_id, rank
+                FROM timers_fts
+                WHERE timers_fts MATCH ?1
+            ) ON match_id = id
+            ORDER BY rank, id DESC
+            LIMIT ?2
+        ");
+        let mut stmt = self.conn.prepare(&q)?;
+        let items = stmt.query_map(rusqlite::params![query, limit], SQLTimerRow::from_row)?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
 
+    fn purge_timers(&self, deleted_before: Option<u64>) -> Result<usize, StorageError> {
+        let ids = "
+            SELECT id FROM timers
+            WHERE status = ?1
+                AND (?2 IS NULL OR deleted_at IS NULL OR deleted_at < ?2)
+        ";
+        let params = rusqlite::params![9, deleted_before];
+        self.record_deleted_timers(ids, params)?;
+        for table in ["timer_sessions", "timer_tags", "pomodoros", "events"] {
+            self.conn.execute(
+                &format!("DELETE FROM {table} WHERE timer_id IN ({ids})"),
+                params
+            )?;
+        }
+        let n = self.conn.execute(&format!("DELETE FROM timers WHERE id IN ({ids})"), params)?;
+        Ok(n)
+    }
 
+    fn remove_timer(&self, id: i64) -> Result<(), StorageError> {
+        self.record_deleted_timers("?1", rusqlite::params![id])?;
+        for table in ["timer_sessions", "timer_tags", "pomodoros", "events"] {
+            self.conn.execute(
+                &format!("DELETE FROM {table} WHERE timer_id = ?1"),
+                rusqlite::params![id]
+            )?;
+        }
+        self.conn.execute("DELETE FROM timers WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn insert_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError> {
+        self.conn.execute("
+            INSERT INTO timer_sessions
+                (timer_id, start, end, uuid, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+            ",
+            rusqlite::params![
+                session.timer_id,
+                session.start,
+                session.end,
+                uuid_or_new(&session.uuid),
+                now()
+            ]
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
 
-    fn setup_storage() -> Storage {
-        let storage = Storage::from_memory().expect("err");
-        let items = vec![
-            SQLTimerRow {
-                id: 0,
-                task: "test1".to_string(),
-                start: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
-                end: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
-                idle: 0,
-                status: 1
-            },
-            SQLTimerRow {
-                id: 0,
-                task: "test2".to_string(),
-                start: Storage::str_to_time("2024-01-02 00:00:00".to_string()).expect("err"),
-                end: Storage::str_to_time("2024-01-02 00:00:00".to_string()).expect("err"),
-                idle: 0,
-                status: 1
-            },
-            SQLTimerRow {
+    /// `updated_at` is changed only if the session is.
+    fn update_session(&self, session: &SQLSessionRow) -> Result<(), StorageError> {
+        self.conn.execute("
+            UPDATE timer_sessions SET
+                updated_at = CASE
+                    WHEN timer_id IS ?1 AND start IS ?2 AND end IS ?3 THEN updated_at
+                    ELSE ?5
+                END,
+                timer_id=?1, start=?2, end=?3
+            WHERE id=?4
+            ",
+            rusqlite::params![
+                session.timer_id,
+                session.start,
+                session.end,
+                session.id,
+                now()
+            ]
+        )?;
+        Ok(())
+    }
+
+    fn merge_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError> {
+        let id = self.conn.query_row("
+            INSERT INTO timer_sessions
+                (timer_id, start, end, uuid, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (uuid) DO UPDATE SET
+                timer_id=excluded.timer_id, start=excluded.start, end=excluded.end,
+                updated_at=excluded.updated_at
+            RETURNING id
+            ",
+            rusqlite::params![
+                session.timer_id,
+                session.start,
+                session.end,
+                session.uuid,
+                session.updated_at
+            ],
+            | r | r.get(0)
+        )?;
+        self.forget_deleted(&session.uuid)?;
+        Ok(id)
+    }
+
+    fn get_session_by_uuid(&self, uuid: &str) -> Result<Option<SQLSessionRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, start, end, uuid, updated_at
+            FROM timer_sessions
+            WHERE uuid = ?1
+        ";
+        match self.conn.query_row(q, rusqlite::params![uuid], SQLSessionRow::from_row) {
+            Ok(s) => Ok(Some(s)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn get_deleted_uuids(&self) -> Result<Vec<SQLDeletedRow>, StorageError> {
+        let mut stmt = self.conn.prepare("
+            SELECT uuid, kind, deleted_at
+            FROM deleted_uuids
+            ORDER BY deleted_at, uuid
+        ")?;
+        let items = stmt.query_map([], SQLDeletedRow::from_row)?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn insert_deleted_uuid(&self, deleted: &SQLDeletedRow) -> Result<(), StorageError> {
+        self.conn.execute("
+            INSERT INTO deleted_uuids (uuid, kind, deleted_at)
+                VALUES (?1, ?2, ?3)
+            ON CONFLICT (uuid) DO UPDATE SET
+                deleted_at = MAX(deleted_at, excluded.deleted_at)
+            ",
+            rusqlite::params![deleted.uuid, deleted.kind, deleted.deleted_at]
+        )?;
+        Ok(())
+    }
+
+    fn delete_session(&self, id: i64) -> Result<(), StorageError> {
+        self.record_deleted(
+            DELETED_SESSION,
+            "SELECT uuid FROM timer_sessions WHERE id = ?1",
+            rusqlite::params![id]
+        )?;
+        self.conn.execute(
+            "DELETE FROM timer_sessions WHERE id = ?1",
+            rusqlite::params![id]
+        )?;
+        Ok(())
+    }
+
+    fn get_sessions_by_timer(&self, timer_id: i64) -> Result<Vec<SQLSessionRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, start, end, uuid, updated_at
+            FROM timer_sessions
+            WHERE timer_id = ?1
+            ORDER BY start, id
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![timer_id],
+            SQLSessionRow::from_row
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn get_sessions_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLSessionRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, start, end, uuid, updated_at
+            FROM timer_sessions
+            WHERE timer_id IN (SELECT value FROM json_each(?1))
+            ORDER BY timer_id, start, id
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![id_list(timer_ids)],
+            SQLSessionRow::from_row
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn insert_pomodoro(&self, pomodoro: &SQLPomodoroRow) -> Result<i64, StorageError> {
+        self.conn.execute("
+            INSERT INTO pomodoros
+                (timer_id, start, end)
+                VALUES (?1, ?2, ?3)
+            ",
+            rusqlite::params![
+                pomodoro.timer_id,
+                pomodoro.start,
+                pomodoro.end
+            ]
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn get_pomodoros_by_timer(&self, timer_id: i64) -> Result<Vec<SQLPomodoroRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, start, end
+            FROM pomodoros
+            WHERE timer_id = ?1
+            ORDER BY start, id
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![timer_id],
+            SQLPomodoroRow::from_row
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn get_pomodoros_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLPomodoroRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, start, end
+            FROM pomodoros
+            WHERE timer_id IN (SELECT value FROM json_each(?1))
+            ORDER BY timer_id, start, id
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![id_list(timer_ids)],
+            SQLPomodoroRow::from_row
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn add_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+            rusqlite::params![tag]
+        )?;
+        self.conn.execute("
+            INSERT OR IGNORE INTO timer_tags (timer_id, tag_id)
+            SELECT ?1, id FROM tags WHERE name = ?2
+            ",
+            rusqlite::params![timer_id, tag]
+        )?;
+        Ok(())
+    }
+
+    fn remove_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError> {
+        self.conn.execute("
+            DELETE FROM timer_tags
+            WHERE timer_id = ?1
+                AND tag_id IN (SELECT id FROM tags WHERE name = ?2)
+            ",
+            rusqlite::params![timer_id, tag]
+        )?;
+        Ok(())
+    }
+
+    fn get_tags_by_timer(&self, timer_id: i64) -> Result<Vec<String>, StorageError> {
+        let q = "
+            SELECT t.name
+            FROM timer_tags tt
+            JOIN tags t ON t.id = tt.tag_id
+            WHERE tt.timer_id = ?1
+            ORDER BY t.name
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(rusqlite::params![timer_id], | r | r.get(0))?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn get_tags_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<(i64, String)>, StorageError> {
+        let q = "
+            SELECT tt.timer_id, t.name
+            FROM timer_tags tt
+            JOIN tags t ON t.id = tt.tag_id
+            WHERE tt.timer_id IN (SELECT value FROM json_each(?1))
+            ORDER BY tt.timer_id, t.name
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![id_list(timer_ids)],
+            | r | Ok((r.get(0)?, r.get(1)?))
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+    fn restore_snapshot(
+        &self,
+        timer_id: i64,
+        snapshot: Option<&TimerSnapshot>
+    ) -> Result<(), StorageError> {
+        self.record_deleted_timers("?1", rusqlite::params![timer_id])?;
+        self.conn.execute("DELETE FROM timers WHERE id = ?1", rusqlite::params![timer_id])?;
+        self.conn.execute(
+            "DELETE FROM timer_sessions WHERE timer_id = ?1",
+            rusqlite::params![timer_id]
+        )?;
+        self.conn.execute(
+            "DELETE FROM timer_tags WHERE timer_id = ?1",
+            rusqlite::params![timer_id]
+        )?;
+        let Some(snapshot) = snapshot else { return Ok(()) };
+        self.insert_timer(&SQLTimerRow { id: timer_id, ..snapshot.timer.clone() })?;
+        self.forget_deleted(&snapshot.timer.uuid)?;
+        for session in snapshot.sessions.iter() {
+            self.forget_deleted(&session.uuid)?;
+            self.conn.execute("
+                INSERT INTO timer_sessions
+                    (id, timer_id, start, end, uuid, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ",
+                rusqlite::params![
+                    session.id,
+                    timer_id,
+                    session.start,
+                    session.end,
+                    uuid_or_new(&session.uuid),
+                    now()
+                ]
+            )?;
+        }
+        for tag in snapshot.tags.iter() {
+            self.add_timer_tag(timer_id, tag)?;
+        }
+        Ok(())
+    }
+
+    fn insert_event(&self, event: &SQLEventRow) -> Result<i64, StorageError> {
+        self.conn.execute("
+            INSERT INTO events
+                (timer_id, action, before, after, created_at, undone, group_id)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ",
+            rusqlite::params![
+                event.timer_id,
+                event.action,
+                snapshot_to_sql(&event.before)?,
+                snapshot_to_sql(&event.after)?,
+                event.created_at,
+                event.undone,
+                event.group_id
+            ]
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn set_event_undone(&self, id: i64, undone: bool) -> Result<(), StorageError> {
+        self.conn.execute(
+            "UPDATE events SET undone = ?1 WHERE id = ?2",
+            rusqlite::params![undone, id]
+        )?;
+        Ok(())
+    }
+
+    fn delete_undone_events(&self) -> Result<(), StorageError> {
+        self.conn.execute("DELETE FROM events WHERE undone = 1", [])?;
+        Ok(())
+    }
+
+    fn group_events(&self, after_id: i64) -> Result<(), StorageError> {
+        self.conn.execute("
+            UPDATE events
+            SET group_id = (SELECT MIN(id) FROM events WHERE id > ?1 AND undone = 0)
+            WHERE id > ?1 AND undone = 0
+            ",
+            rusqlite::params![after_id]
+        )?;
+        Ok(())
+    }
+
+    fn get_next_event(&self, undone: bool) -> Result<Option<SQLEventRow>, StorageError> {
+        let q = "
+            SELECT id, timer_id, action, before, after, created_at, undone, group_id
+            FROM events
+            WHERE undone = ?1
+            ORDER BY CASE WHEN ?1 THEN id ELSE -id END
+            LIMIT 1
+        ";
+        match self.conn.query_row(q, rusqlite::params![undone], SQLEventRow::from_row) {
+            Ok(e) => Ok(Some(e)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn insert_project(&self, name: &str) -> Result<i64, StorageError> {
+        self.conn.execute(
+            "INSERT INTO projects (name, archived) VALUES (?1, 0)",
+            rusqlite::params![name]
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn update_project(&self, project: &SQLProjectRow) -> Result<(), StorageError> {
+        self.conn.execute(
+            "UPDATE projects SET name=?1, archived=?2 WHERE id=?3",
+            rusqlite::params![project.name, project.archived, project.id]
+        )?;
+        Ok(())
+    }
+
+    fn get_project_by_id(&self, id: i64) -> Result<SQLProjectRow, StorageError> {
+        match self.conn.query_row(
+            "SELECT id, name, archived FROM projects WHERE id = ?1",
+            rusqlite::params![id],
+            SQLProjectRow::from_row
+        ) {
+            Ok(p) => Ok(p),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(StorageError::ProjectDoesNotExists),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn get_project_by_name(&self, name: &str) -> Result<SQLProjectRow, StorageError> {
+        match self.conn.query_row(
+            "SELECT id, name, archived FROM projects WHERE name = ?1",
+            rusqlite::params![name],
+            SQLProjectRow::from_row
+        ) {
+            Ok(p) => Ok(p),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(StorageError::ProjectDoesNotExists),
+            Err(e) => Err(StorageError::DatabaseError(e))
+        }
+    }
+
+    fn get_projects(&self, with_archived: bool) -> Result<Vec<SQLProjectRow>, StorageError> {
+        let q = "
+            SELECT id, name, archived
+            FROM projects
+            WHERE ?1 OR archived = 0
+            ORDER BY name
+        ";
+        let mut stmt = self.conn.prepare(q)?;
+        let items = stmt.query_map(
+            rusqlite::params![with_archived],
+            SQLProjectRow::from_row
+        )?;
+        Ok(items.collect::<Result<_, _>>()?)
+    }
+
+}
+
+
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod tests {
+    use super::*;
+
+    fn setup_storage() -> Storage {
+        let storage = Storage::from_memory().expect("err");
+        let items = vec![
+            SQLTimerRow {
+                id: 0,
+                task: "test1".to_string(),
+                start: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
+                end: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
+                idle: 0,
+                status: 1,
+                project_id: None,
+                ..SQLTimerRow::default()
+            },
+            SQLTimerRow {
+                id: 0,
+                task: "test2".to_string(),
+                start: Storage::str_to_time("2024-01-02 00:00:00".to_string()).expect("err"),
+                end: Storage::str_to_time("2024-01-02 00:00:00".to_string()).expect("err"),
+                idle: 0,
+                status: 1,
+                project_id: None,
+                ..SQLTimerRow::default()
+            },
+            SQLTimerRow {
                 id: 0,
                 task: "test3".to_string(),
                 start: Storage::str_to_time("2024-01-03 00:00:00".to_string()).expect("err"),
                 end: Storage::str_to_time("2024-01-03 00:00:00".to_string()).expect("err"),
                 idle: 0,
-                status: 1
+                status: 1,
+                project_id: None,
+                ..SQLTimerRow::default()
             },
             SQLTimerRow {
                 id: 0,
@@ -336,7 +1431,9 @@ mod tests {
                 start: Storage::str_to_time("2024-01-04 00:00:00".to_string()).expect("err"),
                 end: Storage::str_to_time("2024-01-04 00:00:00".to_string()).expect("err"),
                 idle: 0,
-                status: 2
+                status: 2,
+                project_id: Some(1),
+                ..SQLTimerRow::default()
             }
         ];
         for item in items {
@@ -362,7 +1459,9 @@ mod tests {
             start: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
             end: Storage::str_to_time("2024-01-01 00:00:00".to_string()).expect("err"),
             idle: 0,
-            status: 1
+            status: 1,
+            project_id: None,
+            ..SQLTimerRow::default()
         };
         let storage = Storage::from_memory().expect("err");
         let id = storage.insert_timer(&row).expect("Problem");
@@ -383,29 +1482,395 @@ mod tests {
         assert_eq!(items.len(), 3);
     }
 
+    #[test]
+    fn test_pages() {
+        let storage = setup_storage();
+        let mut row = storage.get_timer_by_id(2).unwrap();
+        row.start = storage.get_timer_by_id(3).unwrap().start;
+        storage.update_timer(&row).expect("err");
+        let filter = TimerFilter::default();
+        let ids = | page: TimerPage | storage.get_timers_page(&filter, &page)
+            .expect("err")
+            .iter()
+            .map(| t | t.id)
+            .collect::<Vec<_>>();
+        let all = ids(TimerPage::default());
+        assert_eq!(all[..3], [4, 3, 2]);
+        assert_eq!(ids(TimerPage { limit: 2, offset: 1, after_id: None }), vec![3, 2]);
+        assert_eq!(ids(TimerPage { limit: 2, offset: 0, after_id: Some(3) }), vec![2, 1]);
+        assert_eq!(ids(TimerPage { limit: -1, offset: 0, after_id: Some(1) }), all[4..]);
+        assert_eq!(
+            storage.get_timers_page(&filter, &TimerPage { after_id: Some(100), ..TimerPage::default() }).err(),
+            Some(StorageError::TimerDoesNotExists)
+        );
+    }
+
+    #[test]
+    fn test_indexes() {
+        let storage = setup_storage();
+        let plan = | q: &str | storage.conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {q}")).unwrap()
+            .query_map([], | row | row.get::<_, String>(3)).unwrap()
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>()
+            .join("; ");
+        assert!(plan("SELECT * FROM timers WHERE start >= 1 ORDER BY start DESC, id DESC").contains("timers_start"));
+        assert!(plan("SELECT * FROM timers ORDER BY end DESC LIMIT 5").contains("timers_end"));
+        assert!(plan("SELECT * FROM timers WHERE status = 1 ORDER BY id DESC").contains("timers_status"));
+        assert!(plan("SELECT * FROM timer_sessions WHERE timer_id = 1 ORDER BY start").contains("timer_sessions_timer"));
+    }
+
     #[test]
     fn test_select_by_date() {
         let storage = setup_storage();
         let items = storage.get_timers_by_date(
             -1,
-            Some("2024-01-02".to_string()),
-            None
+            &TimerFilter {
+                date_from: Some("2024-01-02".to_string()),
+                ..TimerFilter::default()
+            }
         ).unwrap();
         assert_eq!(items.len(), 3);
 
         let items = storage.get_timers_by_date(
             -1,
-            None,
-            Some("2024-01-03".to_string()),
+            &TimerFilter {
+                date_to: Some("2024-01-03".to_string()),
+                ..TimerFilter::default()
+            }
         ).unwrap();
         assert_eq!(items.len(), 2);
 
         let items = storage.get_timers_by_date(
             -1,
-            Some("2024-01-02".to_string()),
-            Some("2024-01-03".to_string())
+            &TimerFilter {
+                date_from: Some("2024-01-02".to_string()),
+                date_to: Some("2024-01-03".to_string()),
+                ..TimerFilter::default()
+            }
+        ).unwrap();
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn test_select_by_project() {
+        let storage = setup_storage();
+        storage.insert_project("work").expect("err");
+        let items = storage.get_timers_by_date(
+            -1,
+            &TimerFilter { project: Some("work".to_string()), ..TimerFilter::default() }
         ).unwrap();
         assert_eq!(items.len(), 1);
+        assert_eq!(items[0].task, "test4");
+
+        let items = storage.get_timers_by_date(
+            -1,
+            &TimerFilter { project: Some("home".to_string()), ..TimerFilter::default() }
+        );
+        assert_eq!(items.err(), Some(StorageError::ProjectDoesNotExists));
+    }
+
+    #[test]
+    fn test_tags() {
+        let storage = setup_storage();
+        storage.add_timer_tag(1, "review").expect("err");
+        storage.add_timer_tag(1, "review").expect("err");
+        storage.add_timer_tag(1, "coding").expect("err");
+        storage.add_timer_tag(2, "meeting").expect("err");
+        storage.add_timer_tag(3, "coding").expect("err");
+        assert_eq!(storage.get_tags_by_timer(1).unwrap(), vec!["coding", "review"]);
+
+        let select = | include: &[&str], exclude: &[&str] | {
+            let filter = TimerFilter {
+                tags_include: include.iter().map(| t | t.to_string()).collect(),
+                tags_exclude: exclude.iter().map(| t | t.to_string()).collect(),
+                ..TimerFilter::default()
+            };
+            let mut ids: Vec<i64> = storage.get_timers_by_date(-1, &filter)
+                .unwrap()
+                .iter()
+                .map(| t | t.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(select(&["coding"], &[]), vec![1, 3]);
+        assert_eq!(select(&["coding", "meeting"], &[]), vec![1, 2, 3]);
+        assert_eq!(select(&[], &["coding"]), vec![2, 4]);
+        assert_eq!(select(&["coding"], &["review"]), vec![3]);
+
+        storage.remove_timer_tag(1, "review").expect("err");
+        assert_eq!(storage.get_tags_by_timer(1).unwrap(), vec!["coding"]);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let storage = setup_storage();
+        storage.add_timer_tag(1, "review").expect("err");
+        storage.insert_session(&SQLSessionRow {
+            id: 0,
+            timer_id: 1,
+            start: 10,
+            end: Some(20),
+            ..SQLSessionRow::default()
+        }).expect("err");
+        let snapshot = storage.get_snapshot(1).expect("err").unwrap();
+        assert!(storage.get_snapshot(300).expect("err").is_none());
+
+        storage.restore_snapshot(1, None).expect("err");
+        assert!(storage.get_timer_by_id(1).is_err());
+        assert!(storage.get_sessions_by_timer(1).unwrap().is_empty());
+        assert!(storage.get_tags_by_timer(1).unwrap().is_empty());
+
+        storage.restore_snapshot(1, Some(&snapshot)).expect("err");
+        assert_eq!(storage.get_timer_by_id(1).unwrap().task, "test1");
+        assert_eq!(storage.get_sessions_by_timer(1).unwrap().len(), 1);
+        assert_eq!(storage.get_tags_by_timer(1).unwrap(), vec!["review"]);
+    }
+
+    #[test]
+    fn test_events() {
+        let storage = setup_storage();
+        for action in ["new", "start", "stop"] {
+            storage.insert_event(&SQLEventRow {
+                id: 0,
+                timer_id: 1,
+                action: action.to_string(),
+                before: None,
+                after: storage.get_snapshot(1).unwrap(),
+                created_at: 0,
+                undone: false,
+                group_id: None
+            }).expect("err");
+        }
+        let last = storage.get_next_event(false).unwrap().unwrap();
+        assert_eq!(last.action, "stop");
+        assert_eq!(last.after.unwrap().timer.task, "test1");
+        assert!(storage.get_next_event(true).unwrap().is_none());
+
+        storage.set_event_undone(last.id, true).expect("err");
+        storage.set_event_undone(last.id - 1, true).expect("err");
+        assert_eq!(storage.get_next_event(false).unwrap().unwrap().action, "new");
+        assert_eq!(storage.get_next_event(true).unwrap().unwrap().action, "start");
+
+        storage.delete_undone_events().expect("err");
+        assert!(storage.get_next_event(true).unwrap().is_none());
+
+        let first = storage.get_next_event(false).unwrap().unwrap();
+        storage.group_events(first.id).expect("err");
+        assert_eq!(storage.get_next_event(false).unwrap().unwrap().group_id, None);
+        storage.group_events(0).expect("err");
+        assert_eq!(storage.get_next_event(false).unwrap().unwrap().group_id, Some(first.id));
+    }
+
+    #[test]
+    fn test_projects() {
+        let storage = Storage::from_memory().expect("err");
+        let id = storage.insert_project("work").expect("err");
+        storage.insert_project("home").expect("err");
+        assert!(storage.insert_project("work").is_err());
+
+        let mut project = storage.get_project_by_name("work").expect("err");
+        assert_eq!(project.id, id);
+        project.name = "job".to_string();
+        project.archived = true;
+        storage.update_project(&project).expect("err");
+
+        assert_eq!(storage.get_projects(false).unwrap().len(), 1);
+        assert_eq!(storage.get_projects(true).unwrap().len(), 2);
+        assert_eq!(storage.get_project_by_id(id).unwrap().name, "job");
+        match storage.get_project_by_name("work") {
+            Err(StorageError::ProjectDoesNotExists) => {},
+            _ => panic!("project must be renamed")
+        }
+    }
+
+    const MIGRATIONS_AFTER_V1: usize = SCHEMA_VERSION as usize - 1;
+
+    #[test]
+    fn test_backup_restore() {
+        let path = std::env::temp_dir().join(format!("focus_timer_backup_{}.db", std::process::id()));
+        let mut storage = setup_storage();
+        storage.backup_to(&path).expect("err");
+        storage.update_timer(&SQLTimerRow {
+            task: "changed".to_string(),
+            ..storage.get_timer_by_id(1).unwrap()
+        }).expect("err");
+
+        let copy = Storage::open(path.clone()).expect("err");
+        assert_eq!(copy.version().unwrap(), SCHEMA_VERSION);
+        assert_ne!(copy.get_timer_by_id(1).unwrap().task, "changed");
+        drop(copy);
+
+        storage.restore_from(&path).expect("err");
+        assert_ne!(storage.get_timer_by_id(1).unwrap().task, "changed");
+        std::fs::remove_file(&path).expect("err");
+    }
+
+    #[test]
+    fn test_search() {
+        let storage = Storage::from_memory().expect("err");
+        for task in ["Review of the release notes", "code review", "preview", "\"odd\" -task*"] {
+            storage.insert_timer(&SQLTimerRow { task: task.to_string(), ..SQLTimerRow::default() })
+                .expect("err");
+        }
+        let ids = | rows: Vec<SQLTimerRow> | rows.iter().map(| t | t.id).collect::<Vec<_>>();
+        assert_eq!(ids(storage.search_timers("review", -1).expect("err")), vec![2, 1]);
+        assert_eq!(ids(storage.search_timers("rev rel", -1).expect("err")), vec![1]);
+        assert_eq!(ids(storage.search_timers("\"odd\" -task*", -1).expect("err")), vec![4]);
+        assert!(storage.search_timers("", -1).expect("err").is_empty());
+
+        storage.update_timer(&SQLTimerRow {
+            task: "planning".to_string(),
+            ..storage.get_timer_by_id(2).expect("err")
+        }).expect("err");
+        storage.restore_snapshot(1, None).expect("err");
+        assert!(storage.search_timers("review", -1).expect("err").is_empty());
+        assert_eq!(ids(storage.search_timers("plan", -1).expect("err")), vec![2]);
+    }
+
+    #[test]
+    fn test_search_after_upgrade() {
+        let conn = Connection::open_in_memory().expect("err");
+        for m in migrations::MIGRATIONS.iter().take(4) {
+            conn.execute_batch(m.sql).expect("err");
+        }
+        conn.execute_batch("
+            INSERT INTO db_params (param, value_int) VALUES ('version', 4);
+            INSERT INTO timers (task, start, end, idle, status) VALUES ('old review', 0, 0, 0, 2);
+        ").expect("err");
+        let storage = Storage::from_connection(conn, None);
+        storage.migrate().expect("err");
+        assert_eq!(storage.search_timers("review", -1).expect("err").len(), 1);
+    }
+
+    #[test]
+    fn test_problems() {
+        let storage = Storage::from_memory().expect("err");
+        for (start, status) in [(0, 2), (100, 3), (200, 0), (300, 1), (400, 2), (500, 9)] {
+            storage.insert_timer(&SQLTimerRow {
+                task: format!("task {start}"),
+                start,
+                end: start + 50,
+                idle: 10,
+                status,
+                status_before_delete: (status == 9).then_some(2),
+                ..SQLTimerRow::default()
+            }).expect("err");
+        }
+        assert!(storage.find_problems().expect("err").is_empty());
+        storage.conn.execute_batch("
+            UPDATE timers SET status = 7 WHERE id = 1;
+            UPDATE timers SET status = -3 WHERE id = 3;
+            UPDATE timers SET end = start - 10 WHERE id = 2;
+            UPDATE timers SET idle = -5 WHERE id = 3;
+            UPDATE timers SET status = 1 WHERE id = 5;
+            UPDATE timers SET status_before_delete = -4 WHERE id = 6;
+            INSERT INTO timers_fts (timers_fts, rowid, task) VALUES ('delete', 4, 'task 300');
+        ").expect("err");
+        assert!(storage.get_timers_by_date(-1, &TimerFilter::default()).is_err());
+        let problems = storage.find_problems().expect("err");
+        assert_eq!(problems, vec![
+            DbProblem::SearchIndexBroken,
+            DbProblem::UnknownStatus(1, 7),
+            DbProblem::UnknownStatus(3, -3),
+            DbProblem::UnknownStatusBeforeDelete(6, -4),
+            DbProblem::EndBeforeStart(2),
+            DbProblem::NegativeIdle(3),
+            DbProblem::IdleExceedsSpan(2),
+            DbProblem::ExtraRunning(4)
+        ]);
+        for p in problems.iter() {
+            assert_eq!(storage.fix_problem(p).expect("err"), !matches!(p, DbProblem::ExtraRunning(_)));
+        }
+        assert_eq!(storage.find_problems().expect("err"), vec![DbProblem::ExtraRunning(4)]);
+        assert_eq!(storage.get_timer_by_id(1).unwrap().status, 2);
+        assert_eq!(storage.get_timer_by_id(3).unwrap().status, 2);
+        assert_eq!(storage.get_timer_by_id(6).unwrap().status_before_delete, Some(2));
+        assert_eq!(storage.get_timers_by_date(-1, &TimerFilter::default()).expect("err").len(), 6);
+        assert_eq!(storage.get_timer_by_id(2).unwrap().idle, 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let storage = setup_storage();
+        let row = storage.get_timer_by_id(1).expect("err");
+        assert_eq!(row.uuid.len(), 36);
+        storage.update_timer(&SQLTimerRow { task: "changed".to_string(), uuid: String::new(), ..row.clone() })
+            .expect("err");
+        assert_eq!(storage.get_timer_by_uuid(&row.uuid).expect("err").task, "changed");
+
+        let id = storage.merge_timer(&SQLTimerRow { task: "merged".to_string(), updated_at: 5, ..row.clone() })
+            .expect("err");
+        assert_eq!(id, 1);
+        let merged = storage.get_timer_by_id(1).expect("err");
+        assert_eq!((merged.task.as_str(), merged.updated_at), ("merged", 5));
+        let id = storage.merge_timer(&SQLTimerRow { uuid: "other".to_string(), ..row })
+            .expect("err");
+        assert_ne!(id, 1);
+
+        let session = SQLSessionRow { timer_id: 1, start: 10, uuid: "s".to_string(), ..SQLSessionRow::default() };
+        let id = storage.merge_session(&session).expect("err");
+        assert_eq!(storage.merge_session(&SQLSessionRow { end: Some(20), ..session }).expect("err"), id);
+        assert_eq!(storage.get_sessions_by_timer(1).expect("err")[0].end, Some(20));
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let storage = Storage::from_memory().expect("err");
+        storage.set_version(SCHEMA_VERSION + 1).expect("err");
+        assert_eq!(storage.migrate().err(), Some(StorageError::SchemaVersionError));
+    }
+
+    #[test]
+    fn test_migrate_file() {
+        let path = std::env::temp_dir().join(format!("focus_timer_migrate_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).expect("err");
+        conn.execute_batch(migrations::MIGRATIONS[0].sql).expect("err");
+        conn.execute_batch("
+            INSERT INTO db_params (param, value_int) VALUES ('version', 1);
+            INSERT INTO timers (start, task, end, idle, status) VALUES (0, 'old', 10, 0, 2);
+        ").expect("err");
+        drop(conn);
+
+        let storage = Storage::open(path.clone()).expect("err");
+        assert_eq!(storage.version().unwrap(), 1);
+        let backup = storage.migrate().expect("err").expect("backup is not taken");
+        assert_eq!(storage.version().unwrap(), SCHEMA_VERSION);
+        assert_eq!(storage.get_timer_by_id(1).unwrap().task, "old");
+        assert_eq!(storage.migrate().expect("err"), None);
+
+        let old = Storage::open(backup.clone()).expect("err");
+        assert_eq!(old.version().unwrap(), 1);
+        std::fs::remove_file(&path).expect("err");
+        std::fs::remove_file(&backup).expect("err");
+    }
+
+    #[test]
+    fn test_upgrade_v1() {
+        let conn = Connection::open_in_memory().expect("err");
+        conn.execute_batch("
+            CREATE TABLE db_params (
+                param STRING, value_int INTEGER, value_str STRING, value_float FLOAT
+            );
+            CREATE TABLE timers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                start INTEGER, task STRING, end INTEGER, idle INTEGER, status INTEGER
+            );
+            INSERT INTO db_params (param, value_int) VALUES ('version', 1);
+            INSERT INTO timers (start, task, end, idle, status) VALUES (0, 'old', 10, 0, 2);
+        ").expect("err");
+        let storage = Storage::from_connection(conn, None);
+        assert_eq!(storage.pending_migrations().unwrap().len(), MIGRATIONS_AFTER_V1);
+        storage.migrate().expect("err");
+        assert_eq!(storage.get_version().unwrap(), Some(SCHEMA_VERSION));
+        assert!(storage.pending_migrations().unwrap().is_empty());
+        let timer = storage.get_timer_by_id(1).expect("err");
+        assert_eq!(timer.task, "old");
+        assert_eq!(timer.project_id, None);
+        assert_eq!(timer.deleted_at, None);
+        assert_eq!(timer.completed_at, None);
     }
 
     #[test]
@@ -430,4 +1895,146 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_atomic() {
+        let storage = setup_storage();
+        let result: Result<(), StorageError> = storage.atomic(|| {
+            let mut item = storage.get_timer_by_id(1)?;
+            item.task = "changed".to_string();
+            storage.update_timer(&item)?;
+            storage.get_timer_by_id(300)?;
+            Ok(())
+        });
+        assert_eq!(result, Err(StorageError::TimerDoesNotExists));
+        assert_eq!(storage.get_timer_by_id(1).unwrap().task, "test1");
+
+        let result: Result<(), StorageError> = storage.atomic(|| {
+            let mut item = storage.get_timer_by_id(1)?;
+            item.task = "changed".to_string();
+            storage.update_timer(&item)
+        });
+        assert!(result.is_ok());
+        assert_eq!(storage.get_timer_by_id(1).unwrap().task, "changed");
+    }
+
+    #[test]
+    fn test_purge() {
+        let storage = setup_storage();
+        for (id, deleted_at) in [(1, Some(100)), (2, Some(200)), (3, None)] {
+            let mut item = storage.get_timer_by_id(id).unwrap();
+            item.status = 9;
+            item.deleted_at = deleted_at;
+            item.status_before_delete = Some(1);
+            storage.update_timer(&item).expect("err");
+            storage.add_timer_tag(id, "tag").expect("err");
+        }
+        assert_eq!(storage.get_timer_by_id(1).unwrap().status_before_delete, Some(1));
+
+        assert_eq!(storage.purge_timers(Some(150)).expect("err"), 2);
+        assert!(storage.get_timer_by_id(1).is_err());
+        assert!(storage.get_tags_by_timer(1).unwrap().is_empty());
+        assert!(storage.get_timer_by_id(2).is_ok());
+        assert_eq!(storage.purge_timers(None).expect("err"), 1);
+        assert!(storage.get_timer_by_id(4).is_ok());
+    }
+
+    #[test]
+    fn test_params() {
+        let storage = Storage::from_memory().expect("err");
+        assert_eq!(storage.param_str("retention").expect("err"), None);
+        storage.set_param_str("retention", Some("30d")).expect("err");
+        storage.set_param_int("last", Some(100)).expect("err");
+        storage.set_param_str("retention", Some("60d")).expect("err");
+        assert_eq!(storage.param_str("retention").expect("err"), Some(String::from("60d")));
+        assert_eq!(storage.param_int("last").expect("err"), Some(100));
+        storage.set_param_str("retention", None).expect("err");
+        assert_eq!(storage.param_str("retention").expect("err"), None);
+        assert_eq!(storage.version().expect("err"), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_sessions() {
+        let storage = setup_storage();
+        let mut session = SQLSessionRow {
+            id: 0,
+            timer_id: 1,
+            start: Storage::str_to_time("2024-01-01 10:00".to_string()).expect("err"),
+            end: None,
+            ..SQLSessionRow::default()
+        };
+        session.id = storage.insert_session(&session).expect("err");
+        storage.insert_session(&SQLSessionRow {
+            id: 0,
+            timer_id: 1,
+            start: Storage::str_to_time("2024-01-01 09:00".to_string()).expect("err"),
+            end: Some(Storage::str_to_time("2024-01-01 09:30".to_string()).expect("err")),
+            ..SQLSessionRow::default()
+        }).expect("err");
+        session.end = Some(session.start + 60);
+        storage.update_session(&session).expect("err");
+
+        let items = storage.get_sessions_by_timer(1).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].end, Some(items[0].start + 1800));
+        assert_eq!(items[1].end, Some(session.start + 60));
+        assert!(storage.get_sessions_by_timer(2).unwrap().is_empty());
+
+        storage.delete_session(items[0].id).expect("err");
+        assert_eq!(storage.get_sessions_by_timer(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_pomodoros() {
+        let storage = setup_storage();
+        let start = Storage::str_to_time("2024-01-01 10:00".to_string()).expect("err");
+        for timer_id in [1, 1, 2] {
+            storage.insert_pomodoro(&SQLPomodoroRow {
+                id: 0,
+                timer_id,
+                start,
+                end: start + 1500
+            }).expect("err");
+        }
+        assert_eq!(storage.get_pomodoros_by_timer(1).unwrap().len(), 2);
+        assert_eq!(storage.get_pomodoros_by_timer(2).unwrap().len(), 1);
+        assert!(storage.get_pomodoros_by_timer(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_by_timers() {
+        let storage = setup_storage();
+        let start = Storage::str_to_time("2024-01-01 10:00".to_string()).expect("err");
+        for (timer_id, offset) in [(2, 0), (1, 3600), (1, 0), (3, 0)] {
+            storage.insert_session(&SQLSessionRow {
+                timer_id,
+                start: start + offset,
+                end: Some(start + offset + 60),
+                ..SQLSessionRow::default()
+            }).expect("err");
+            storage.insert_pomodoro(&SQLPomodoroRow {
+                id: 0,
+                timer_id,
+                start: start + offset,
+                end: start + offset + 60
+            }).expect("err");
+        }
+        storage.add_timer_tag(2, "review").expect("err");
+        storage.add_timer_tag(1, "review").expect("err");
+        storage.add_timer_tag(1, "coding").expect("err");
+
+        let sessions = storage.get_sessions_by_timers(&[2, 1]).unwrap();
+        let keys: Vec<(i64, u64)> = sessions.iter().map(| s | (s.timer_id, s.start - start)).collect();
+        assert_eq!(keys, vec![(1, 0), (1, 3600), (2, 0)]);
+        assert_eq!(storage.get_pomodoros_by_timers(&[1, 3]).unwrap().len(), 3);
+        assert_eq!(
+            storage.get_tags_by_timers(&[1, 2]).unwrap(),
+            vec![
+                (1, String::from("coding")),
+                (1, String::from("review")),
+                (2, String::from("review"))
+            ]
+        );
+        assert!(storage.get_sessions_by_timers(&[]).unwrap().is_empty());
+    }
+
 }
@@ -0,0 +1,187 @@
+use chrono::Utc;
+use uuid::Uuid;
+use crate::storage::{
+    SQLTimerRow,
+    SQLSessionRow,
+    SQLPomodoroRow,
+    SQLProjectRow,
+    SQLEventRow,
+    SQLDeletedRow,
+    TimerSnapshot,
+    TimerFilter,
+    TimerPage,
+    StorageError
+};
+
+
+pub(crate) fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+/// Keeps the uuid of a stored row, a new one is made for a new row.
+pub(crate) fn uuid_or_new(uuid: &str) -> String {
+    if uuid.is_empty() {
+        Uuid::new_v4().to_string()
+    } else {
+        uuid.to_string()
+    }
+}
+
+/// Everything the timer logic needs from a storage backend.
+/// `Storage` keeps the data in SQLite, `MemoryStore` in memory
+/// or in a JSON file.
+pub trait TimerStore {
+
+    /// Runs `f` so that either all its changes are saved or none of them,
+    /// changes are rolled back if it returns an error.
+    fn atomic<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<StorageError>;
+
+    fn is_timer_exist(&self, id: i64) -> Result<bool, StorageError>;
+
+    /// Inserts a timer, a new id is assigned when `timer.id` is 0
+    /// and a new uuid when `timer.uuid` is empty. Inserts and updates
+    /// of timers and sessions set `updated_at` to the current time.
+    fn insert_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError>;
+
+    fn update_timer(&self, timer: &SQLTimerRow) -> Result<(), StorageError>;
+
+    fn get_timer_by_id(&self, id: i64) -> Result<SQLTimerRow, StorageError>;
+
+    fn get_timer_by_uuid(&self, uuid: &str) -> Result<SQLTimerRow, StorageError>;
+
+    /// Inserts or, if there is a timer with the same uuid, replaces it
+    /// keeping `updated_at` as it is. `timer.id` is ignored,
+    /// the id of the stored timer is returned.
+    fn merge_timer(&self, timer: &SQLTimerRow) -> Result<i64, StorageError>;
+
+    fn count_timers_by_status(&self, status: u32) -> Result<u64, StorageError>;
+
+    /// Newest first, a negative `limit` returns all of them.
+    fn get_timers_by_status(
+        &self,
+        status: u32,
+        limit: i32
+    ) -> Result<Vec<SQLTimerRow>, StorageError>;
+
+    /// Timers which ended last.
+    fn get_last_timers(&self, limit: u64) -> Result<Vec<SQLTimerRow>, StorageError>;
+
+    /// Timers matching the filter, latest started first,
+    /// a negative `limit` returns all of them.
+    fn get_timers_by_date(
+        &self,
+        limit: i32,
+        filter: &TimerFilter
+    ) -> Result<Vec<SQLTimerRow>, StorageError> {
+        self.get_timers_page(filter, &TimerPage { limit, ..TimerPage::default() })
+    }
+
+    /// A page of `get_timers_by_date`, timers started at the same time
+    /// are ordered by id. Fails if the `after_id` timer does not exist.
+    fn get_timers_page(
+        &self,
+        filter: &TimerFilter,
+        page: &TimerPage
+    ) -> Result<Vec<SQLTimerRow>, StorageError>;
+
+    /// Timers whose task contains every word of `query` (as a word prefix),
+    /// best matches first.
+    fn search_timers(&self, query: &str, limit: i32) -> Result<Vec<SQLTimerRow>, StorageError>;
+
+    /// Removes deleted timers with everything attached to them,
+    /// only the ones deleted before `deleted_before` if it is given.
+    fn purge_timers(&self, deleted_before: Option<u64>) -> Result<usize, StorageError>;
+
+    /// Removes a timer with its sessions, tags, pomodoros and events.
+    /// Removed timers and sessions are recorded in `get_deleted_uuids`,
+    /// a row stored again with `merge_*` or `restore_snapshot` is taken out of it.
+    fn remove_timer(&self, id: i64) -> Result<(), StorageError>;
+
+    fn insert_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError>;
+
+    fn update_session(&self, session: &SQLSessionRow) -> Result<(), StorageError>;
+
+    /// Same as `merge_timer` for a session.
+    fn merge_session(&self, session: &SQLSessionRow) -> Result<i64, StorageError>;
+
+    fn delete_session(&self, id: i64) -> Result<(), StorageError>;
+
+    fn get_session_by_uuid(&self, uuid: &str) -> Result<Option<SQLSessionRow>, StorageError>;
+
+    /// Timers and sessions removed for good, oldest first.
+    fn get_deleted_uuids(&self) -> Result<Vec<SQLDeletedRow>, StorageError>;
+
+    /// Records a deletion made in another database, the later time is kept.
+    fn insert_deleted_uuid(&self, deleted: &SQLDeletedRow) -> Result<(), StorageError>;
+
+    /// Sessions of a timer ordered by start.
+    fn get_sessions_by_timer(&self, timer_id: i64) -> Result<Vec<SQLSessionRow>, StorageError>;
+
+    /// Sessions of all the timers ordered by timer and start.
+    fn get_sessions_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLSessionRow>, StorageError>;
+
+    fn insert_pomodoro(&self, pomodoro: &SQLPomodoroRow) -> Result<i64, StorageError>;
+
+    fn get_pomodoros_by_timer(&self, timer_id: i64) -> Result<Vec<SQLPomodoroRow>, StorageError>;
+
+    /// Pomodoros of all the timers ordered by timer and start.
+    fn get_pomodoros_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<SQLPomodoroRow>, StorageError>;
+
+    fn add_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError>;
+
+    fn remove_timer_tag(&self, timer_id: i64, tag: &str) -> Result<(), StorageError>;
+
+    /// Tag names of a timer in alphabetical order.
+    fn get_tags_by_timer(&self, timer_id: i64) -> Result<Vec<String>, StorageError>;
+
+    /// Timer ids with tag names of all the timers, ordered by timer and name.
+    fn get_tags_by_timers(&self, timer_ids: &[i64]) -> Result<Vec<(i64, String)>, StorageError>;
+
+    fn get_snapshot(&self, timer_id: i64) -> Result<Option<TimerSnapshot>, StorageError> {
+        let timer = match self.get_timer_by_id(timer_id) {
+            Ok(t) => t,
+            Err(StorageError::TimerDoesNotExists) => return Ok(None),
+            Err(e) => return Err(e)
+        };
+        Ok(Some(TimerSnapshot {
+            timer,
+            sessions: self.get_sessions_by_timer(timer_id)?,
+            tags: self.get_tags_by_timer(timer_id)?
+        }))
+    }
+
+    /// Puts a timer with its sessions and tags back to the saved state,
+    /// `None` removes the timer completely.
+    fn restore_snapshot(
+        &self,
+        timer_id: i64,
+        snapshot: Option<&TimerSnapshot>
+    ) -> Result<(), StorageError>;
+
+    fn insert_event(&self, event: &SQLEventRow) -> Result<i64, StorageError>;
+
+    fn set_event_undone(&self, id: i64, undone: bool) -> Result<(), StorageError>;
+
+    /// Forgets undone events, they cannot be redone after a new change.
+    fn delete_undone_events(&self) -> Result<(), StorageError>;
+
+    /// Puts the events recorded after event `after_id` into one group.
+    fn group_events(&self, after_id: i64) -> Result<(), StorageError>;
+
+    /// Latest event to undo or, with `undone`, the earliest one to redo.
+    fn get_next_event(&self, undone: bool) -> Result<Option<SQLEventRow>, StorageError>;
+
+    fn insert_project(&self, name: &str) -> Result<i64, StorageError>;
+
+    fn update_project(&self, project: &SQLProjectRow) -> Result<(), StorageError>;
+
+    fn get_project_by_id(&self, id: i64) -> Result<SQLProjectRow, StorageError>;
+
+    fn get_project_by_name(&self, name: &str) -> Result<SQLProjectRow, StorageError>;
+
+    /// Projects by name, archived ones only with `with_archived`.
+    fn get_projects(&self, with_archived: bool) -> Result<Vec<SQLProjectRow>, StorageError>;
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeSet;
+use crate::storage::{SQLTimerRow, SQLSessionRow, TimerFilter, StorageError, DELETED_TIMER};
+use crate::store::TimerStore;
+use crate::timer::{Timer, TimerStatus};
+
+
+/// What `sync` changed, counts are over both databases.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub copied: usize,
+    pub updated: usize,
+    pub sessions: usize,
+    pub deleted: usize,
+    pub conflicts: Vec<String>
+}
+
+fn find_timer(store: &impl TimerStore, uuid: &str) -> Result<Option<SQLTimerRow>, StorageError> {
+    match store.get_timer_by_uuid(uuid) {
+        Ok(t) => Ok(Some(t)),
+        Err(StorageError::TimerDoesNotExists) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+fn project_name(store: &impl TimerStore, timer: &SQLTimerRow) -> Result<Option<String>, StorageError> {
+    match timer.project_id {
+        Some(id) => Ok(Some(store.get_project_by_id(id)?.name)),
+        None => Ok(None)
+    }
+}
+
+/// Differences in start, end and idle are not compared,
+/// they follow from sessions which are merged on their own.
+fn same_timer(
+    a: &impl TimerStore,
+    x: &SQLTimerRow,
+    b: &impl TimerStore,
+    y: &SQLTimerRow
+) -> Result<bool, StorageError> {
+    Ok(x.task == y.task
+        && x.status == y.status
+        && x.deleted_at == y.deleted_at
+        && x.status_before_delete == y.status_before_delete
+        && x.completed_at == y.completed_at
+        && x.reopened_at == y.reopened_at
+        && project_name(a, x)? == project_name(b, y)?)
+}
+
+/// Writes `timer` of `src` into `dst` under the same uuid,
+/// the project is matched by name and created if `dst` has none.
+fn put_timer(
+    src: &impl TimerStore,
+    dst: &impl TimerStore,
+    timer: &SQLTimerRow
+) -> Result<i64, StorageError> {
+    let project_id = match project_name(src, timer)? {
+        Some(name) => match dst.get_project_by_name(&name) {
+            Ok(p) => Some(p.id),
+            Err(StorageError::ProjectDoesNotExists) => Some(dst.insert_project(&name)?),
+            Err(e) => return Err(e)
+        },
+        None => None
+    };
+    dst.merge_timer(&SQLTimerRow { id: 0, project_id, ..timer.clone() })
+}
+
+fn put_session(
+    dst: &impl TimerStore,
+    timer_id: i64,
+    session: &SQLSessionRow
+) -> Result<(), StorageError> {
+    dst.merge_session(&SQLSessionRow { id: 0, timer_id, ..session.clone() })?;
+    Ok(())
+}
+
+/// Copies sessions missing on one side and replaces older versions
+/// of the same session, returns the number of sessions written.
+fn merge_sessions(
+    a: &impl TimerStore,
+    a_id: i64,
+    b: &impl TimerStore,
+    b_id: i64
+) -> Result<usize, StorageError> {
+    let a_sessions = a.get_sessions_by_timer(a_id)?;
+    let b_sessions = b.get_sessions_by_timer(b_id)?;
+    let same = | x: &SQLSessionRow, y: &SQLSessionRow | x.start == y.start && x.end == y.end;
+    let mut n = 0;
+    for x in a_sessions.iter().filter(| s | !s.uuid.is_empty()) {
+        match b_sessions.iter().find(| y | y.uuid == x.uuid) {
+            Some(y) if same(x, y) || y.updated_at > x.updated_at => {},
+            _ => {
+                put_session(b, b_id, x)?;
+                n += 1;
+            }
+        }
+    }
+    for y in b_sessions.iter().filter(| s | !s.uuid.is_empty()) {
+        match a_sessions.iter().find(| x | x.uuid == y.uuid) {
+            Some(x) if same(x, y) || x.updated_at >= y.updated_at => {},
+            _ => {
+                put_session(a, a_id, y)?;
+                n += 1;
+            }
+        }
+    }
+    Ok(n)
+}
+
+/// Removes from `dst` the timers and sessions removed in `src`, unless
+/// they were changed after that, and records these deletions in `dst`
+/// for its next syncs. Returns the number of rows removed.
+fn apply_deletions(src: &impl TimerStore, dst: &impl TimerStore) -> Result<usize, StorageError> {
+    let mut n = 0;
+    let mut deletions = src.get_deleted_uuids()?;
+    // sessions of a removed timer go with it
+    deletions.sort_by_key(| d | d.kind != DELETED_TIMER);
+    for deleted in deletions {
+        let changed_since = if deleted.kind == DELETED_TIMER {
+            match find_timer(dst, &deleted.uuid)? {
+                Some(t) if t.updated_at > deleted.deleted_at => true,
+                Some(t) => {
+                    dst.remove_timer(t.id)?;
+                    n += 1;
+                    false
+                },
+                None => false
+            }
+        } else {
+            match dst.get_session_by_uuid(&deleted.uuid)? {
+                Some(s) if s.updated_at > deleted.deleted_at => true,
+                Some(s) => {
+                    dst.delete_session(s.id)?;
+                    n += 1;
+                    false
+                },
+                None => false
+            }
+        };
+        if !changed_since {
+            dst.insert_deleted_uuid(&deleted)?;
+        }
+    }
+    Ok(n)
+}
+
+/// Recomputes start, end and idle of a timer from its merged sessions,
+/// `updated_at` is kept so the next sync does not see a change.
+/// Returns `false` if the sessions overlap.
+fn sync_span(store: &impl TimerStore, id: i64) -> Result<bool, StorageError> {
+    let row = store.get_timer_by_id(id)?;
+    let sessions = store.get_sessions_by_timer(id)?;
+    if sessions.is_empty() {
+        return Ok(true);
+    }
+    let disjoint = sessions.windows(2).all(| w | w[0].end.is_some_and(| end | end <= w[1].start));
+    let mut timer = Timer::try_from(row.clone())?.with_sessions(sessions);
+    timer.sync_span();
+    let synced = timer.to_sqlite_row();
+    if (synced.start, synced.end, synced.idle) != (row.start, row.end, row.idle) {
+        store.merge_timer(&SQLTimerRow {
+            start: synced.start,
+            end: synced.end,
+            idle: synced.idle,
+            ..row
+        })?;
+    }
+    Ok(disjoint)
+}
+
+/// Makes both databases hold the same timers. Timers and sessions are
+/// matched by uuid, the one changed last wins. Tags are united.
+/// Rows removed on one side are removed on the other one first,
+/// unless they were changed there later.
+/// Timers changed differently on both sides are reported as conflicts.
+pub fn sync(a: &impl TimerStore, b: &impl TimerStore) -> Result<SyncReport, StorageError> {
+    let mut report = SyncReport::default();
+    report.deleted += apply_deletions(a, b)?;
+    report.deleted += apply_deletions(b, a)?;
+    let filter = TimerFilter::default();
+    let uuids: BTreeSet<String> = a.get_timers_by_date(-1, &filter)?
+        .into_iter()
+        .chain(b.get_timers_by_date(-1, &filter)?)
+        .map(| t | t.uuid)
+        .filter(| uuid | !uuid.is_empty())
+        .collect();
+
+    for uuid in uuids.iter() {
+        let (a_id, b_id) = match (find_timer(a, uuid)?, find_timer(b, uuid)?) {
+            (Some(x), None) => {
+                report.copied += 1;
+                (x.id, put_timer(a, b, &x)?)
+            },
+            (None, Some(y)) => {
+                report.copied += 1;
+                (put_timer(b, a, &y)?, y.id)
+            },
+            (Some(x), Some(y)) => {
+                if !same_timer(a, &x, b, &y)? {
+                    report.updated += 1;
+                    let kept = if y.updated_at > x.updated_at {
+                        put_timer(b, a, &y)?;
+                        "other"
+                    } else {
+                        put_timer(a, b, &x)?;
+                        "this"
+                    };
+                    report.conflicts.push(format!(
+                        "Task {} \"{}\" differs, the version of {kept} database is kept",
+                        x.id, x.task
+                    ));
+                }
+                (x.id, y.id)
+            },
+            (None, None) => continue
+        };
+        for tag in a.get_tags_by_timer(a_id)? {
+            b.add_timer_tag(b_id, &tag)?;
+        }
+        for tag in b.get_tags_by_timer(b_id)? {
+            a.add_timer_tag(a_id, &tag)?;
+        }
+        report.sessions += merge_sessions(a, a_id, b, b_id)?;
+        let disjoint = sync_span(a, a_id)?;
+        sync_span(b, b_id)?;
+        if !disjoint {
+            report.conflicts.push(format!("Task {a_id}: sessions overlap"));
+        }
+    }
+    if a.count_timers_by_status(TimerStatus::RUN as u32)? > 1 {
+        report.conflicts.push(String::from("Several tasks are running, see `db check`"));
+    }
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    fn timer(store: &impl TimerStore, task: &str, start: u64) -> SQLTimerRow {
+        let id = store.insert_timer(&SQLTimerRow {
+            task: task.to_string(),
+            start,
+            end: start + 100,
+            status: TimerStatus::PAUSED as u32,
+            ..SQLTimerRow::default()
+        }).unwrap();
+        store.insert_session(&SQLSessionRow {
+            timer_id: id,
+            start,
+            end: Some(start + 100),
+            ..SQLSessionRow::default()
+        }).unwrap();
+        store.get_timer_by_id(id).unwrap()
+    }
+
+    #[test]
+    fn test_copy() {
+        let a = MemoryStore::new();
+        let b = MemoryStore::new();
+        let x = timer(&a, "laptop", 1000);
+        a.insert_project("work").unwrap();
+        a.update_timer(&SQLTimerRow { project_id: Some(1), ..x.clone() }).unwrap();
+        a.add_timer_tag(x.id, "review").unwrap();
+        b.insert_project("home").unwrap();
+        timer(&b, "desktop", 5000);
+
+        let report = sync(&a, &b).unwrap();
+        assert_eq!(report.copied, 2);
+        assert!(report.conflicts.is_empty());
+        let copy = b.get_timer_by_uuid(&x.uuid).unwrap();
+        assert_eq!(b.get_project_by_id(copy.project_id.unwrap()).unwrap().name, "work");
+        assert_eq!(b.get_tags_by_timer(copy.id).unwrap(), vec!["review"]);
+        assert_eq!(b.get_sessions_by_timer(copy.id).unwrap().len(), 1);
+        assert_eq!(a.count_timers_by_status(TimerStatus::PAUSED as u32).unwrap(), 2);
+
+        let report = sync(&a, &b).unwrap();
+        assert_eq!((report.copied, report.updated, report.sessions), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_last_writer_wins() {
+        let a = MemoryStore::new();
+        let b = MemoryStore::new();
+        let x = timer(&a, "task", 1000);
+        sync(&a, &b).unwrap();
+        let y = b.get_timer_by_uuid(&x.uuid).unwrap();
+
+        a.merge_timer(&SQLTimerRow { task: "old".to_string(), updated_at: 10, ..x.clone() }).unwrap();
+        b.merge_timer(&SQLTimerRow { task: "new".to_string(), updated_at: 20, ..y.clone() }).unwrap();
+        let session = b.get_sessions_by_timer(y.id).unwrap()[0].clone();
+        b.merge_session(&SQLSessionRow {
+            end: Some(1500),
+            updated_at: session.updated_at + 1,
+            ..session
+        }).unwrap();
+        b.insert_session(&SQLSessionRow {
+            timer_id: y.id,
+            start: 2000,
+            end: Some(2100),
+            ..SQLSessionRow::default()
+        }).unwrap();
+
+        let report = sync(&a, &b).unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.conflicts.len(), 1);
+        let x = a.get_timer_by_uuid(&x.uuid).unwrap();
+        assert_eq!(x.task, "new");
+        assert_eq!((x.start, x.end, x.idle), (1000, 2100, 500));
+        assert_eq!(a.get_sessions_by_timer(x.id).unwrap().len(), 2);
+    }
+}
@@ -1,22 +1,38 @@
 use std::fmt;
 use std::fs;
 use std::io::Write;
-use serde;
 use serde::Serialize;
 use std::error::Error;
-use chrono::{DateTime, Utc};
-use crate::SQLTimerRow;
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, Utc};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use crate::{SQLTimerRow, SQLSessionRow, SQLPomodoroRow, StorageError};
+use crate::chart;
 
 
 #[derive(Debug, PartialEq)]
 pub enum TimerError {
     TimerHasFiniteState,
+    WrongInterval,
+    WrongIdle,
+    SessionsOverlap,
+    TimerIsRunning,
+    TimerIsNotDeleted,
+    TimerIsNotCompleted,
+    UnknownStatus(u32),
 }
 
 impl fmt::Display for TimerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             TimerError::TimerHasFiniteState => write!(f, "This timer cannot be changed"),
+            TimerError::WrongInterval => write!(f, "End of interval must be after its start"),
+            TimerError::WrongIdle => write!(f, "Idle time must be between zero and the timer span"),
+            TimerError::SessionsOverlap => write!(f, "Interval overlaps existing session"),
+            TimerError::TimerIsRunning => write!(f, "Stop the timer before changing its time"),
+            TimerError::TimerIsNotDeleted => write!(f, "This timer is not deleted"),
+            TimerError::TimerIsNotCompleted => write!(f, "This timer is not completed"),
+            TimerError::UnknownStatus(status) => write!(f, "Unknown timer status {status}"),
         }
     }
 }
@@ -53,20 +69,78 @@ impl Serialize for TimerStatus {
         }
 }
 
-impl From<u32> for TimerStatus {
-    fn from(n: u32) -> TimerStatus {
+impl TryFrom<u32> for TimerStatus {
+    type Error = TimerError;
+
+    fn try_from(n: u32) -> Result<TimerStatus, TimerError> {
         match n {
-            0 => TimerStatus::NEW,
-            1 => TimerStatus::RUN,
-            2 => TimerStatus::PAUSED,
-            3 => TimerStatus::COMPLETED,
-            9 => TimerStatus::DELETED,
-            _ => panic!("not supported value")
+            0 => Ok(TimerStatus::NEW),
+            1 => Ok(TimerStatus::RUN),
+            2 => Ok(TimerStatus::PAUSED),
+            3 => Ok(TimerStatus::COMPLETED),
+            9 => Ok(TimerStatus::DELETED),
+            _ => Err(TimerError::UnknownStatus(n))
         }
     }
 }
 
 
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: i64,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>
+}
+
+impl From<SQLSessionRow> for Session {
+    fn from(row: SQLSessionRow) -> Self {
+        Self {
+            id: row.id,
+            start: DateTime::from_timestamp(row.start as i64, 0).unwrap(),
+            end: row.end.map(| t | DateTime::from_timestamp(t as i64, 0).unwrap())
+        }
+    }
+}
+
+impl Session {
+
+    pub fn new(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Self {
+        Self { id: 0, start, end }
+    }
+
+    pub fn duration(&self) -> i64 {
+        let end = self.end.unwrap_or_else(Utc::now);
+        end.timestamp() - self.start.timestamp()
+    }
+
+    pub fn to_sqlite_row(&self, timer_id: i64) -> SQLSessionRow {
+        SQLSessionRow {
+            id: self.id,
+            timer_id,
+            start: self.start.timestamp() as u64,
+            end: self.end.map(| t | t.timestamp() as u64),
+            ..SQLSessionRow::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pomodoro {
+    pub id: i64,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>
+}
+
+impl From<SQLPomodoroRow> for Pomodoro {
+    fn from(row: SQLPomodoroRow) -> Self {
+        Self {
+            id: row.id,
+            start: DateTime::from_timestamp(row.start as i64, 0).unwrap(),
+            end: DateTime::from_timestamp(row.end as i64, 0).unwrap()
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Timer {
     pub id: i64,
@@ -76,7 +150,24 @@ pub struct Timer {
     #[serde(serialize_with="serialize_datetime")] 
     pub end: DateTime<Utc>,
     pub idle: i64,
-    pub status: TimerStatus
+    pub status: TimerStatus,
+    #[serde(skip)]
+    pub project_id: Option<i64>,
+    pub project: Option<String>,
+    #[serde(serialize_with="serialize_tags")]
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub status_before_delete: Option<TimerStatus>,
+    #[serde(serialize_with="serialize_opt_datetime")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(serialize_with="serialize_opt_datetime")]
+    pub reopened_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub sessions: Vec<Session>,
+    #[serde(skip)]
+    pub pomodoros: Vec<Pomodoro>
 }
 
 fn serialize_datetime<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
@@ -86,12 +177,44 @@ where
         serializer.serialize_str(&formatted)
     }
 
-impl From<SQLTimerRow> for Timer {
-    fn from(row: SQLTimerRow) -> Self {
+fn serialize_opt_datetime<S>(
+    dt: &Option<DateTime<Utc>>,
+    serializer: S
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer {
+        match dt {
+            Some(dt) => serialize_datetime(dt, serializer),
+            None => serializer.serialize_str("")
+        }
+    }
+
+fn serialize_tags<S>(tags: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer {
+        serializer.serialize_str(&tags.join(";"))
+    }
+
+/// Fails on a status the app does not know, `db check --fix` repairs such rows.
+impl TryFrom<SQLTimerRow> for Timer {
+    type Error = StorageError;
+
+    fn try_from(row: SQLTimerRow) -> Result<Self, StorageError> {
+        let unknown = | status | StorageError::UnknownStatus(row.id, status);
+        let status = TimerStatus::try_from(row.status).map_err(|_| unknown(row.status))?;
+        let status_before_delete = row.status_before_delete
+            .map(| s | TimerStatus::try_from(s).map_err(|_| unknown(s)))
+            .transpose()?;
         let start = DateTime::from_timestamp(row.start as i64, 0).unwrap();
         let end = DateTime::from_timestamp(row.end as i64, 0).unwrap();
-        let status = TimerStatus::from(row.status);
-        Self::new(row.id, row.task, start, end, row.idle, status)
+        let mut timer = Self::new(row.id, row.task, start, end, row.idle, status);
+        timer.project_id = row.project_id;
+        timer.deleted_at = row.deleted_at.map(| t | DateTime::from_timestamp(t as i64, 0).unwrap());
+        timer.status_before_delete = status_before_delete;
+        timer.completed_at = row.completed_at.map(| t | DateTime::from_timestamp(t as i64, 0).unwrap());
+        timer.reopened_at = row.reopened_at.map(| t | DateTime::from_timestamp(t as i64, 0).unwrap());
+        timer.sessions = timer.legacy_sessions();
+        Ok(timer)
     }
 }
 
@@ -112,22 +235,77 @@ impl Timer {
         idle: i64,
         status: TimerStatus
     ) -> Self {
-        Self { id, task, start, end, idle, status }
+        Self {
+            id,
+            task,
+            start,
+            end,
+            idle,
+            status,
+            project_id: None,
+            project: None,
+            tags: Vec::new(),
+            deleted_at: None,
+            status_before_delete: None,
+            completed_at: None,
+            reopened_at: None,
+            sessions: Vec::new(),
+            pomodoros: Vec::new()
+        }
+    }
+
+    /// Replaces sessions with the ones stored for this timer.
+    /// Timers created before sessions existed have none stored,
+    /// for them the session rebuilt from start/end/idle is kept.
+    pub fn with_sessions(mut self, sessions: Vec<SQLSessionRow>) -> Self {
+        if !sessions.is_empty() {
+            self.sessions = sessions.into_iter().map(Session::from).collect();
+        }
+        self
+    }
+
+    pub fn with_pomodoros(mut self, pomodoros: Vec<SQLPomodoroRow>) -> Self {
+        self.pomodoros = pomodoros.into_iter().map(Pomodoro::from).collect();
+        self
+    }
+
+    fn legacy_sessions(&self) -> Vec<Session> {
+        match self.status {
+            TimerStatus::NEW => Vec::new(),
+            TimerStatus::RUN => vec![
+                Session::new(self.start + chrono::Duration::seconds(self.idle), None)
+            ],
+            _ => {
+                let end = self.end - chrono::Duration::seconds(self.idle);
+                vec![Session::new(self.start, Some(end.max(self.start)))]
+            }
+        }
+    }
+
+    /// Recomputes start, end and idle from sessions.
+    /// End is the moment the timer was last started or stopped,
+    /// idle is the time between sessions.
+    pub(crate) fn sync_span(&mut self) {
+        let (first, last) = match (self.sessions.first(), self.sessions.last()) {
+            (Some(first), Some(last)) => (first.start, last.end.unwrap_or(last.start)),
+            _ => return
+        };
+        let worked: i64 = self.sessions
+            .iter()
+            .filter(| s | s.end.is_some())
+            .map(| s | s.duration())
+            .sum();
+        self.start = first;
+        self.end = last;
+        self.idle = (last.timestamp() - first.timestamp() - worked).max(0);
     }
 
     pub fn set_start(&mut self) -> Result<(), TimerError>  {
         match self.status {
             TimerStatus::DELETED | TimerStatus::COMPLETED => Err(TimerError::TimerHasFiniteState),
-            TimerStatus::NEW => {
-                let now = Utc::now();
-                self.start = now;
-                self.end = now;
-                self.status = TimerStatus::RUN;
-                Ok(()) 
-            },
-            TimerStatus::PAUSED => {
-                let now = Utc::now(); 
-                self.idle = now.timestamp() - self.end.timestamp();
+            TimerStatus::NEW | TimerStatus::PAUSED => {
+                self.sessions.push(Session::new(Utc::now(), None));
+                self.sync_span();
                 self.status = TimerStatus::RUN;
                 Ok(())
             },
@@ -145,23 +323,167 @@ impl Timer {
         }
         if self.status == TimerStatus::NEW { self.set_start()? };
         if self.status == TimerStatus::PAUSED { return Ok(()) };
-        self.end = Utc::now();
+        let now = Utc::now();
+        if let Some(session) = self.sessions.last_mut() {
+            if session.end.is_none() {
+                session.end = Some(now.max(session.start));
+            }
+        }
+        self.sync_span();
         self.status = TimerStatus::PAUSED;
         Ok(())
     }
 
+    /// Records a finished session in the past, e.g. work done
+    /// without running the timer. A new timer becomes paused.
+    pub fn add_session(
+        &mut self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> Result<(), TimerError> {
+        if self.status == TimerStatus::DELETED {
+            return Err(TimerError::TimerHasFiniteState);
+        }
+        if end <= start {
+            return Err(TimerError::WrongInterval);
+        }
+        let overlaps = self.sessions.iter().any(| s | {
+            s.start < end && start < s.end.unwrap_or_else(Utc::now)
+        });
+        if overlaps {
+            return Err(TimerError::SessionsOverlap);
+        }
+        let pos = self.sessions.partition_point(| s | s.start < start);
+        self.sessions.insert(pos, Session::new(start, Some(end)));
+        self.sync_span();
+        if self.status == TimerStatus::NEW {
+            self.status = TimerStatus::PAUSED;
+        }
+        Ok(())
+    }
+
+    /// Corrects start, end and idle time, missing values are kept.
+    /// Sessions are cut to the new span and then shortened or extended
+    /// from the latest one until the worked time is `end - start - idle`.
+    pub fn set_span(
+        &mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        idle: Option<i64>
+    ) -> Result<(), TimerError> {
+        if self.status == TimerStatus::DELETED {
+            return Err(TimerError::TimerHasFiniteState);
+        }
+        if self.status == TimerStatus::RUN {
+            return Err(TimerError::TimerIsRunning);
+        }
+        let start = start.unwrap_or(self.start);
+        let end = end.unwrap_or(self.end);
+        if end <= start {
+            return Err(TimerError::WrongInterval);
+        }
+        let span = end.timestamp() - start.timestamp();
+        if let Some(idle) = idle {
+            if idle < 0 || idle > span {
+                return Err(TimerError::WrongIdle);
+            }
+        }
+
+        let mut sessions: Vec<Session> = self.sessions
+            .iter()
+            .filter_map(| s | {
+                let s_end = s.end.unwrap_or(s.start).min(end);
+                let s_start = s.start.max(start);
+                (s_start < s_end).then_some(Session { id: s.id, start: s_start, end: Some(s_end) })
+            })
+            .collect();
+        match sessions.first_mut() {
+            Some(first) => first.start = start,
+            None => sessions.push(Session::new(start, Some(end)))
+        }
+        let worked: i64 = sessions.iter().map(| s | s.duration()).sum();
+        let target = span - idle.unwrap_or(span - worked);
+
+        let mut excess = worked - target;
+        while excess > 0 {
+            let Some(last) = sessions.last_mut() else { break };
+            let cut = excess.min(last.duration());
+            last.end = last.end.map(| e | e - chrono::Duration::seconds(cut));
+            excess -= cut;
+            if last.duration() == 0 {
+                sessions.pop();
+            }
+        }
+        let mut missing = target - worked;
+        let mut next_start = end;
+        for s in sessions.iter_mut().rev() {
+            if missing <= 0 {
+                break;
+            }
+            let s_end = s.end.unwrap_or(s.start);
+            let grow = missing.min(next_start.timestamp() - s_end.timestamp());
+            s.end = Some(s_end + chrono::Duration::seconds(grow));
+            missing -= grow;
+            next_start = s.start;
+        }
+
+        self.sessions = sessions;
+        self.start = start;
+        self.end = end;
+        self.idle = span - target;
+        if self.status == TimerStatus::NEW {
+            self.status = TimerStatus::PAUSED;
+        }
+        Ok(())
+    }
+
+    /// Deleted timers have to be restored first.
     pub fn set_complete(&mut self) -> Result<(), TimerError> {
+        if self.status == TimerStatus::DELETED {
+            return Err(TimerError::TimerHasFiniteState);
+        }
         if self.status == TimerStatus::NEW { self.set_start()?; };
         if self.status == TimerStatus::RUN { self.set_stop()?; };
         self.status = TimerStatus::COMPLETED;
+        if self.completed_at.is_none() {
+            self.completed_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    /// Moves a completed timer back to paused, keeping its sessions.
+    /// `completed_at` keeps the time of the first completion.
+    pub fn set_reopen(&mut self) -> Result<(), TimerError> {
+        if self.status != TimerStatus::COMPLETED {
+            return Err(TimerError::TimerIsNotCompleted);
+        }
+        self.status = TimerStatus::PAUSED;
+        self.reopened_at = Some(Utc::now());
         Ok(())
     }
 
     pub fn set_delete(&mut self) -> Result<(), TimerError> {
+        if self.status == TimerStatus::DELETED {
+            return Ok(());
+        }
+        if self.status == TimerStatus::RUN { self.set_stop()?; };
+        self.status_before_delete = Some(self.status);
+        self.deleted_at = Some(Utc::now());
         self.status = TimerStatus::DELETED;
         Ok(())
     }
 
+    /// Takes a timer out of the trash with the status it had before.
+    pub fn set_restore(&mut self) -> Result<(), TimerError> {
+        if self.status != TimerStatus::DELETED {
+            return Err(TimerError::TimerIsNotDeleted);
+        }
+        self.status = self.status_before_delete.unwrap_or(TimerStatus::PAUSED);
+        self.status_before_delete = None;
+        self.deleted_at = None;
+        Ok(())
+    }
+
     pub fn to_sqlite_row(&self) -> SQLTimerRow {
         SQLTimerRow {
             id: self.id,
@@ -169,20 +491,18 @@ impl Timer {
             start: DateTime::<Utc>::timestamp(&self.start) as u64,
             end: DateTime::<Utc>::timestamp(&self.end) as u64,
             idle: self.idle,
-            status: self.status as u32
+            status: self.status as u32,
+            project_id: self.project_id,
+            deleted_at: self.deleted_at.map(| t | t.timestamp() as u64),
+            status_before_delete: self.status_before_delete.map(| s | s as u32),
+            completed_at: self.completed_at.map(| t | t.timestamp() as u64),
+            reopened_at: self.reopened_at.map(| t | t.timestamp() as u64),
+            ..SQLTimerRow::default()
         }
     }
 
     pub fn time_on(&self) -> i64 {
-        let time = match self.status {
-            TimerStatus::RUN => {
-                Utc::now().timestamp() - self.start.timestamp()
-            },
-            _ => {
-                self.end.timestamp() - self.start.timestamp()
-            }
-        };
-        time - self.idle
+        self.sessions.iter().map(| s | s.duration()).sum()
     }
     
     fn print_time_on(time: i64) {
@@ -235,23 +555,158 @@ impl Timer {
         println!("\n=========================");
         println!("id: {}", self.id);
         println!("Current task: {}", self.task);
+        if let Some(project) = &self.project {
+            println!("Project: {}", project);
+        }
+        if !self.tags.is_empty() {
+            println!("Tags: {}", self.tags.join(", "));
+        }
         println!("Started at: {}", self.start);
         println!("Status: {}", self.status);
+        if let Some(completed_at) = self.completed_at {
+            println!("First completed at: {}", completed_at);
+        }
+        if let Some(reopened_at) = self.reopened_at {
+            println!("Reopened at: {}", reopened_at);
+        }
+        if let Some(deleted_at) = self.deleted_at {
+            println!("Deleted at: {}", deleted_at);
+        }
         print!("Spent: ");
         Timer::print_time_on(self.time_on());
         println!("\n=========================");
     }
+
+    pub fn print_sessions(&self) {
+        println!("=== Sessions of task {} ===", self.id);
+        if self.sessions.is_empty() {
+            println!("No sessions");
+        }
+        for s in self.sessions.iter() {
+            match s.end {
+                Some(end) => print!("{} - {}: ", s.start, end),
+                None => print!("{} - now: ", s.start)
+            }
+            Timer::print_time_on(s.duration());
+        }
+    }
+}
+
+/// Length of the buckets of a report, all of them are in UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(format!("Unknown period {s}, use day, week or month"))
+        }
+    }
 }
 
+impl Period {
+
+    /// Start of the period `t` is in, weeks start on Monday.
+    pub fn start_of(&self, t: DateTime<Utc>) -> DateTime<Utc> {
+        let day = t.date_naive();
+        let first = match self {
+            Period::Day => day,
+            Period::Week => day - Days::new(day.weekday().num_days_from_monday() as u64),
+            Period::Month => day.with_day(1).unwrap_or(day)
+        };
+        first.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    /// Start of the period after the one starting at `start`.
+    pub fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Period::Day => start + Days::new(1),
+            Period::Week => start + Days::new(7),
+            Period::Month => start + Months::new(1)
+        }
+    }
+
+    pub fn label(&self, start: DateTime<Utc>) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d %a").to_string(),
+            Period::Week => start.format("%G-W%V (from %Y-%m-%d)").to_string(),
+            Period::Month => start.format("%Y-%m").to_string()
+        }
+    }
+}
+
+/// Spread of the time spent on tasks, in seconds.
+#[derive(Debug, PartialEq)]
+pub struct TimeDistribution {
+    pub n: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub std_dev: f64,
+    pub shortest: i64,
+    pub longest: i64
+}
+
+/// Value below which `p` percent of `sorted` values lie,
+/// interpolated between the two closest ones.
+fn percentile(sorted: &[i64], p: f64) -> f64 {
+    let rank = (sorted.len() - 1) as f64 * p / 100.0;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] as f64 + (sorted[high] - sorted[low]) as f64 * (rank - low as f64)
+}
+
+impl TimeDistribution {
+
+    /// `None` for no values.
+    pub fn new(values: &[i64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<i64>() as f64 / n as f64;
+        let variance = sorted.iter().map(| v | (*v as f64 - mean).powi(2)).sum::<f64>() / n as f64;
+        Some(TimeDistribution {
+            n,
+            mean,
+            median: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            std_dev: variance.sqrt(),
+            shortest: sorted[0],
+            longest: sorted[n - 1]
+        })
+    }
+}
+
+/// Number of tasks in the chart of `stat --chart`.
+pub const CHART_TASKS: usize = 10;
+
+/// Start of a period with the time spent on each task in it.
+pub type ReportPeriod<'a> = (DateTime<Utc>, Vec<(&'a Timer, i64)>);
+
 pub struct TimerCollection {
     items: Vec<Timer>
 }
 
-impl From<Vec<SQLTimerRow>> for TimerCollection {
-    fn from(items: Vec<SQLTimerRow>) -> Self {
-        Self {
-            items: items.into_iter().map(Timer::from).collect()
-        }
+impl From<Vec<Timer>> for TimerCollection {
+    fn from(items: Vec<Timer>) -> Self {
+        Self { items }
+    }
+}
+
+impl Default for TimerCollection {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -272,11 +727,59 @@ impl TimerCollection {
             .collect()
     }
 
+    /// Deleted timers, hidden by `items`.
+    pub fn trash(&self) -> Vec<&Timer> {
+        self.items
+            .iter()
+            .filter(| x | x.status == TimerStatus::DELETED)
+            .collect()
+    }
+
     pub fn print_items(&self) {
         self.items().iter().for_each(| t | t.print());
     }
 
-    pub fn print_stat(&self) {
+    /// Share of the tasks which are completed, `None` if there are none.
+    pub fn completion_ratio(&self) -> Option<f64> {
+        let items = self.items();
+        if items.is_empty() {
+            return None;
+        }
+        let n_compl = items.iter().filter(| t | t.status == TimerStatus::COMPLETED).count();
+        Some(n_compl as f64 / items.len() as f64)
+    }
+
+    /// Distribution of time over the tasks with any time tracked,
+    /// with `completed` only over the completed ones.
+    pub fn time_distribution(&self, completed: bool) -> Option<TimeDistribution> {
+        let values: Vec<i64> = self.items().iter()
+            .filter(| t | !completed || t.status == TimerStatus::COMPLETED)
+            .map(| t | t.time_on())
+            .filter(| time_on | *time_on > 0)
+            .collect();
+        TimeDistribution::new(&values)
+    }
+
+    /// Number of tasks and time spent per status.
+    pub fn time_by_status(&self) -> Vec<(TimerStatus, usize, i64)> {
+        [TimerStatus::NEW, TimerStatus::RUN, TimerStatus::PAUSED, TimerStatus::COMPLETED]
+            .into_iter()
+            .map(| status | {
+                let items: Vec<&Timer> = self.items().into_iter().filter(| t | t.status == status).collect();
+                (status, items.len(), items.iter().map(| t | t.time_on()).sum())
+            })
+            .filter(| (_, n, _) | *n > 0)
+            .collect()
+    }
+
+    /// The timer with the longest session and that session's length.
+    pub fn longest_session(&self) -> Option<(&Timer, i64)> {
+        self.items().into_iter()
+            .flat_map(| t | t.sessions.iter().map(move | s | (t, s.duration())))
+            .max_by_key(| (_, duration) | *duration)
+    }
+
+    pub fn print_stat(&self, by_tag: bool) {
         let mut n = 0;
         let mut time_on = 0;
         let mut time_on_compl = 0;
@@ -292,8 +795,12 @@ impl TimerCollection {
         println!("==>> TOTAL STAT <<==");
         println!("N tasks: {n}");
         println!("N completed: {n_compl}");
-        if n > 0 {
-            println!("% comletion: {:.1}%", n_compl / n * 100);
+        let n_reopened = self.items().iter().filter(| t | t.reopened_at.is_some()).count();
+        if n_reopened > 0 {
+            println!("N reopened: {n_reopened}");
+        }
+        if let Some(ratio) = self.completion_ratio() {
+            println!("% completion: {:.1}%", ratio * 100.0);
             print!("Total time: ");
             Timer::print_time_on(time_on);
             print!("Avg time: ");
@@ -305,23 +812,268 @@ impl TimerCollection {
             print!("Avg time (Completed): ");
             Timer::print_time_on(time_on_compl / n_compl as i64);
         }
+        self.print_distribution();
+        self.print_status_stat();
+        self.print_project_stat();
+        if by_tag {
+            self.print_tag_stat();
+        }
+        self.print_pomodoro_stat();
+    }
+
+    fn print_distribution(&self) {
+        let Some(all) = self.time_distribution(false) else { return };
+        println!("==>> DISTRIBUTION <<==");
+        println!("N tasks with time: {}", all.n);
+        print!("Median time: ");
+        Timer::print_time_on(all.median.round() as i64);
+        print!("90th percentile: ");
+        Timer::print_time_on(all.p90.round() as i64);
+        print!("Std deviation: ");
+        Timer::print_time_on(all.std_dev.round() as i64);
+        let tracked: Vec<&Timer> = self.items().into_iter().filter(| t | t.time_on() > 0).collect();
+        for (name, time_on) in [("Shortest", all.shortest), ("Longest", all.longest)] {
+            if let Some(t) = tracked.iter().find(| t | t.time_on() == time_on) {
+                print!("{name} task: {} ({}), ", t.task, t.id);
+                Timer::print_time_on(time_on);
+            }
+        }
+        if let Some(completed) = self.time_distribution(true) {
+            print!("Median time (Completed): ");
+            Timer::print_time_on(completed.median.round() as i64);
+            print!("90th percentile (Completed): ");
+            Timer::print_time_on(completed.p90.round() as i64);
+        }
+        if let Some((t, duration)) = self.longest_session() {
+            print!("Longest session: {} ({}), ", t.task, t.id);
+            Timer::print_time_on(duration);
+        }
+    }
+
+    fn print_status_stat(&self) {
+        println!("==>> BY STATUS <<==");
+        for (status, n, time_on) in self.time_by_status() {
+            print!("{status}: {n} tasks, ");
+            Timer::print_time_on(time_on);
+        }
+    }
+
+    /// Number of tasks and time spent per tag. A task with several tags
+    /// is counted under each of them, untagged tasks under an empty name.
+    pub fn time_by_tag(&self) -> BTreeMap<String, (usize, i64)> {
+        let mut tags = BTreeMap::new();
+        for t in self.items().iter() {
+            let time_on = t.time_on();
+            let names = if t.tags.is_empty() { vec![String::new()] } else { t.tags.clone() };
+            for name in names {
+                let entry = tags.entry(name).or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += time_on;
+            }
+        }
+        tags
+    }
+
+    fn print_tag_stat(&self) {
+        println!("==>> BY TAG <<==");
+        for (tag, (n, time_on)) in self.time_by_tag() {
+            let name = if tag.is_empty() { "(no tag)".to_string() } else { tag };
+            print!("{name}: {n} tasks, ");
+            Timer::print_time_on(time_on);
+        }
+    }
+
+    /// Number of tasks and time spent per project name,
+    /// tasks without a project are counted under an empty name.
+    pub fn time_by_project(&self) -> BTreeMap<String, (usize, i64)> {
+        let mut projects = BTreeMap::new();
+        for t in self.items().iter() {
+            let entry = projects
+                .entry(t.project.clone().unwrap_or_default())
+                .or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += t.time_on();
+        }
+        projects
+    }
+
+    fn print_project_stat(&self) {
+        let projects = self.time_by_project();
+        if projects.keys().all(| p | p.is_empty()) {
+            return;
+        }
+        println!("==>> BY PROJECT <<==");
+        for (project, (n, time_on)) in projects {
+            let name = if project.is_empty() { "(no project)".to_string() } else { project };
+            print!("{name}: {n} tasks, ");
+            Timer::print_time_on(time_on);
+        }
+    }
+
+    /// Time spent on every task in each period between `from` and `to`,
+    /// periods and their tasks are ordered by time. Sessions crossing
+    /// the end of a period are split, the parts outside of `from`..`to` are dropped.
+    pub fn report(
+        &self,
+        period: Period,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>
+    ) -> Vec<ReportPeriod<'_>> {
+        let mut buckets: BTreeMap<DateTime<Utc>, BTreeMap<usize, i64>> = BTreeMap::new();
+        let items = self.items();
+        for (n, t) in items.iter().enumerate() {
+            for s in t.sessions.iter() {
+                let mut start = from.map_or(s.start, | from | s.start.max(from));
+                let end = s.end.unwrap_or_else(Utc::now);
+                let end = to.map_or(end, | to | end.min(to));
+                while start < end {
+                    let bucket = period.start_of(start);
+                    let part_end = period.next(bucket).min(end);
+                    *buckets.entry(bucket).or_default().entry(n).or_insert(0) +=
+                        (part_end - start).num_seconds();
+                    start = part_end;
+                }
+            }
+        }
+        buckets.into_iter()
+            .map(| (bucket, tasks) | {
+                let mut tasks: Vec<(&Timer, i64)> = tasks.into_iter()
+                    .map(| (n, time_on) | (items[n], time_on))
+                    .collect();
+                tasks.sort_by_key(| (t, time_on) | (-time_on, t.id));
+                (bucket, tasks)
+            })
+            .collect()
+    }
+
+    pub fn print_report(
+        &self,
+        period: Period,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>
+    ) {
+        let report = self.report(period, from, to);
+        if report.is_empty() {
+            println!("No time tracked");
+        }
+        for (start, tasks) in report {
+            println!("==>> {} <<==", period.label(start));
+            for (t, time_on) in tasks.iter() {
+                print!("{} ({}): ", t.task, t.id);
+                Timer::print_time_on(*time_on);
+            }
+            print!("Total: ");
+            Timer::print_time_on(tasks.iter().map(| (_, time_on) | time_on).sum());
+        }
+    }
+
+    /// Bars of time per task (the longest `CHART_TASKS` ones) and per day
+    /// of the last `days` days with a sparkline of them, at most `width` wide.
+    pub fn print_chart(&self, days: usize, width: usize) {
+        let mut tasks: Vec<(String, i64)> = self.items().iter()
+            .map(| t | (format!("{} ({})", t.task, t.id), t.time_on()))
+            .filter(| (_, time_on) | *time_on > 0)
+            .collect();
+        tasks.sort_by_key(| (_, time_on) | -time_on);
+        tasks.truncate(CHART_TASKS);
+        println!("==>> TIME BY TASK <<==");
+        chart::bar_chart(&tasks, width).iter().for_each(| line | println!("{line}"));
+
+        let days = days.max(1);
+        let per_day = self.last_days(days, Utc::now());
+        println!("==>> LAST {days} DAYS <<==");
+        chart::bar_chart(&per_day, width).iter().for_each(| line | println!("{line}"));
+        let values: Vec<i64> = per_day.iter().map(| (_, time_on) | *time_on).collect();
+        println!("{}", chart::sparkline(&values));
+    }
+
+    /// Seconds tracked on each of `days` days up to the one of `now`,
+    /// labeled by weekday and date. Time after that day is left out.
+    pub fn last_days(&self, days: usize, now: DateTime<Utc>) -> Vec<(String, i64)> {
+        let to = Period::Day.next(Period::Day.start_of(now));
+        let from = to - Days::new(days as u64);
+        let mut per_day: Vec<(String, i64)> = (0..days as u64)
+            .map(| n | ((from + Days::new(n)).format("%a %m-%d").to_string(), 0))
+            .collect();
+        for (day, tasks) in self.report(Period::Day, Some(from), Some(to)) {
+            if let Some(row) = per_day.get_mut((day - from).num_days() as usize) {
+                row.1 += tasks.iter().map(| (_, time_on) | time_on).sum::<i64>();
+            }
+        }
+        per_day
+    }
+
+    /// Seconds tracked per day between `from` and `to`, days without time are left out.
+    pub fn time_by_day(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>
+    ) -> BTreeMap<NaiveDate, i64> {
+        self.report(Period::Day, from, to)
+            .into_iter()
+            .map(| (day, tasks) | (day.date_naive(), tasks.iter().map(| (_, time_on) | time_on).sum()))
+            .filter(| (_, time_on) | *time_on > 0)
+            .collect()
+    }
+
+    pub fn print_heatmap(&self, year: i32) {
+        let start = NaiveDate::from_ymd_opt(year, 1, 1).map(| d | d.and_time(NaiveTime::MIN).and_utc());
+        let days = self.time_by_day(start, start.map(| s | s + Months::new(12)));
+        println!("==>> {year} <<==");
+        chart::heatmap(year, &days).iter().for_each(| line | println!("{line}"));
+        let (mut longest, mut streak) = (0, 0);
+        let mut last: Option<NaiveDate> = None;
+        for day in days.keys() {
+            streak = if last.and_then(| d | d.succ_opt()) == Some(*day) { streak + 1 } else { 1 };
+            longest = longest.max(streak);
+            last = Some(*day);
+        }
+        println!("Days tracked: {}", days.len());
+        println!("Longest streak: {longest} days");
+        print!("Total time: ");
+        Timer::print_time_on(days.values().sum());
+    }
+
+    pub fn pomodoros_by_day(&self) -> BTreeMap<String, usize> {
+        let mut days = BTreeMap::new();
+        for t in self.items().iter() {
+            for p in t.pomodoros.iter() {
+                *days.entry(p.end.format("%Y-%m-%d").to_string()).or_insert(0) += 1;
+            }
+        }
+        days
+    }
+
+    fn print_pomodoro_stat(&self) {
+        let total: usize = self.items().iter().map(| t | t.pomodoros.len()).sum();
+        if total == 0 {
+            return;
+        }
+        println!("==>> POMODOROS <<==");
+        println!("N pomodoros: {total}");
+        println!("-- by task --");
+        for t in self.items().iter().filter(| t | !t.pomodoros.is_empty()) {
+            println!("{} ({}): {}", t.task, t.id, t.pomodoros.len());
+        }
+        println!("-- by day --");
+        for (day, n) in self.pomodoros_by_day() {
+            println!("{day}: {n}");
+        }
     }
 
     pub fn export(&self, path: &str) -> Result<(), Box<dyn Error>> {
         let mut f = fs::File::create(path)?;
-        let mut n = 1;
         writeln!(f, "n,start,end,status,time_on")?;
-        for t in self.items().iter() {
+        for (n, t) in self.items().iter().enumerate() {
             writeln!(
                 f,
                 "{},{},{},{},{}",
-                n,
+                n + 1,
                 t.start,
                 t.end,
                 t.status,
                 t.time_on()
             )?;
-            n += 1;
         };
         Ok(())
     }
@@ -330,6 +1082,7 @@ impl TimerCollection {
 
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use super::*;
     use std::time::Duration;
@@ -399,8 +1152,318 @@ mod tests {
 
         match t.set_start() {
             Err(TimerError::TimerHasFiniteState) => assert!(true),
-            Ok(_) => assert!(false)
+            _ => assert!(false)
         }
 
     }
+
+    #[test]
+    fn test_sessions_accumulate() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut t = Timer::new(1, "test".to_string(), t0, t0, 0, TimerStatus::PAUSED);
+        let row = | start: i64, end: i64 | SQLSessionRow {
+            id: 0,
+            timer_id: 1,
+            start: (t0.timestamp() + start) as u64,
+            end: Some((t0.timestamp() + end) as u64),
+            ..SQLSessionRow::default()
+        };
+        t = t.with_sessions(vec![row(0, 100), row(200, 300), row(1000, 1050)]);
+        t.sync_span();
+        assert_eq!(t.time_on(), 250);
+        assert_eq!(t.idle, 800);
+        assert_eq!(t.end.timestamp() - t.start.timestamp(), 1050);
+    }
+
+    #[test]
+    fn test_pomodoros_by_day() {
+        let row = | id: i64, end: &str | SQLPomodoroRow {
+            id,
+            timer_id: 1,
+            start: 0,
+            end: DateTime::parse_from_rfc3339(end).unwrap().timestamp() as u64
+        };
+        let t1 = Timer::from("a".to_string()).with_pomodoros(vec![
+            row(1, "2024-01-01T10:00:00Z"),
+            row(2, "2024-01-02T10:00:00Z")
+        ]);
+        let t2 = Timer::from("b".to_string()).with_pomodoros(vec![
+            row(3, "2024-01-02T11:00:00Z")
+        ]);
+        let days = TimerCollection::from(vec![t1, t2]).pomodoros_by_day();
+        assert_eq!(days.get("2024-01-01"), Some(&1));
+        assert_eq!(days.get("2024-01-02"), Some(&2));
+    }
+
+    #[test]
+    fn test_time_by_project() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let t1 = DateTime::from_timestamp(1_700_000_100, 0).unwrap();
+        let timer = | project: Option<&str> | {
+            let mut t = Timer::new(1, "t".to_string(), t0, t1, 0, TimerStatus::PAUSED);
+            t.sessions = t.legacy_sessions();
+            t.project = project.map(String::from);
+            t
+        };
+        let collection = TimerCollection::from(vec![
+            timer(Some("work")),
+            timer(Some("work")),
+            timer(None)
+        ]);
+        let projects = collection.time_by_project();
+        assert_eq!(projects.get("work"), Some(&(2, 200)));
+        assert_eq!(projects.get(""), Some(&(1, 100)));
+    }
+
+    #[test]
+    fn test_period() {
+        let t = | s: &str | DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let at = t("2024-02-29T15:30:00Z");
+        assert_eq!(Period::Day.start_of(at), t("2024-02-29T00:00:00Z"));
+        assert_eq!(Period::Week.start_of(at), t("2024-02-26T00:00:00Z"));
+        assert_eq!(Period::Month.start_of(at), t("2024-02-01T00:00:00Z"));
+        assert_eq!(Period::Month.next(t("2024-12-01T00:00:00Z")), t("2025-01-01T00:00:00Z"));
+        assert_eq!(Period::Week.label(t("2024-12-30T00:00:00Z")), "2025-W01 (from 2024-12-30)");
+        assert!("year".parse::<Period>().is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let t = | s: &str | DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let mut a = Timer::from("a".to_string());
+        a.id = 1;
+        a.sessions = vec![
+            Session::new(t("2024-01-31T23:00:00Z"), Some(t("2024-02-01T01:00:00Z"))),
+            Session::new(t("2024-02-01T10:00:00Z"), Some(t("2024-02-01T10:30:00Z")))
+        ];
+        let mut b = Timer::from("b".to_string());
+        b.id = 2;
+        b.sessions = vec![Session::new(t("2024-02-01T12:00:00Z"), Some(t("2024-02-01T14:00:00Z")))];
+        let collection = TimerCollection::from(vec![a, b]);
+        let summary = | period, from, to | collection.report(period, from, to)
+            .iter()
+            .map(| (start, tasks) | (
+                start.format("%Y-%m-%d").to_string(),
+                tasks.iter().map(| (t, time_on) | (t.id, *time_on)).collect::<Vec<_>>()
+            ))
+            .collect::<Vec<_>>();
+
+        assert_eq!(summary(Period::Day, None, None), vec![
+            (String::from("2024-01-31"), vec![(1, 3600)]),
+            (String::from("2024-02-01"), vec![(2, 7200), (1, 5400)])
+        ]);
+        assert_eq!(summary(Period::Month, None, None)[1].1, vec![(2, 7200), (1, 5400)]);
+        assert_eq!(summary(Period::Week, None, None), vec![
+            (String::from("2024-01-29"), vec![(1, 9000), (2, 7200)])
+        ]);
+        assert_eq!(
+            summary(Period::Day, Some(t("2024-02-01T00:00:00Z")), Some(t("2024-02-01T13:00:00Z"))),
+            vec![(String::from("2024-02-01"), vec![(1, 5400), (2, 3600)])]
+        );
+    }
+
+    #[test]
+    fn test_time_by_day() {
+        let t = | s: &str | DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let mut timer = Timer::from("a".to_string());
+        timer.sessions = vec![
+            Session::new(t("2024-12-31T23:00:00Z"), Some(t("2025-01-01T02:00:00Z"))),
+            Session::new(t("2025-01-03T10:00:00Z"), Some(t("2025-01-03T10:30:00Z")))
+        ];
+        let collection = TimerCollection::from(vec![timer]);
+        let days = collection.time_by_day(Some(t("2025-01-01T00:00:00Z")), None);
+        let day = | d | NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        assert_eq!(days, BTreeMap::from([(day(1), 7200), (day(3), 1800)]));
+    }
+
+    #[test]
+    fn test_last_days() {
+        let t = | s: &str | DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let mut timer = Timer::from("a".to_string());
+        timer.sessions = vec![
+            Session::new(t("2025-01-01T23:00:00Z"), Some(t("2025-01-02T01:00:00Z"))),
+            Session::new(t("2025-01-03T10:00:00Z"), Some(t("2025-01-03T11:00:00Z"))),
+            Session::new(t("2030-01-01T10:00:00Z"), Some(t("2030-01-01T11:00:00Z")))
+        ];
+        let collection = TimerCollection::from(vec![timer]);
+        let days = collection.last_days(2, t("2025-01-03T12:00:00Z"));
+        assert_eq!(days, vec![(String::from("Thu 01-02"), 3600), (String::from("Fri 01-03"), 3600)]);
+        collection.print_chart(7, 80);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let d = TimeDistribution::new(&[600, 60, 120, 180, 7200]).unwrap();
+        assert_eq!((d.n, d.shortest, d.longest), (5, 60, 7200));
+        assert_eq!(d.median, 180.0);
+        assert_eq!(d.mean, 1632.0);
+        assert_eq!(d.p90, 4560.0);
+        assert!((d.std_dev - 2790.46).abs() < 0.01);
+        assert_eq!(TimeDistribution::new(&[]), None);
+        assert_eq!(TimeDistribution::new(&[10, 20]).unwrap().median, 15.0);
+    }
+
+    #[test]
+    fn test_stat_by_status() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let timer = | status, seconds | {
+            let mut t = Timer::new(1, "t".to_string(), t0, t0, 0, status);
+            t.sessions = vec![Session::new(t0, Some(t0 + chrono::Duration::seconds(seconds)))];
+            t
+        };
+        let collection = TimerCollection::from(vec![
+            timer(TimerStatus::COMPLETED, 100),
+            timer(TimerStatus::PAUSED, 300),
+            timer(TimerStatus::PAUSED, 200),
+            timer(TimerStatus::DELETED, 1000)
+        ]);
+        assert_eq!(collection.completion_ratio(), Some(1.0 / 3.0));
+        assert_eq!(TimerCollection::new().completion_ratio(), None);
+        assert_eq!(collection.time_by_status(), vec![
+            (TimerStatus::PAUSED, 2, 500),
+            (TimerStatus::COMPLETED, 1, 100)
+        ]);
+        assert_eq!(collection.time_distribution(false).unwrap().median, 200.0);
+        assert_eq!(collection.time_distribution(true).unwrap().median, 100.0);
+        assert_eq!(collection.longest_session().unwrap().1, 300);
+    }
+
+    #[test]
+    fn test_add_session() {
+        let at = | secs: i64 | DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let mut t = Timer::from("test".to_string());
+        t.add_session(at(1000), at(1600)).expect("err");
+        assert_eq!(t.status, TimerStatus::PAUSED);
+        t.add_session(at(0), at(300)).expect("err");
+        assert_eq!(t.time_on(), 900);
+        assert_eq!(t.start, at(0));
+        assert_eq!(t.end, at(1600));
+        assert_eq!(t.idle, 700);
+        assert_eq!(t.add_session(at(200), at(400)), Err(TimerError::SessionsOverlap));
+        assert_eq!(t.add_session(at(400), at(400)), Err(TimerError::WrongInterval));
+        t.status = TimerStatus::DELETED;
+        assert_eq!(t.add_session(at(2000), at(2100)), Err(TimerError::TimerHasFiniteState));
+    }
+
+    #[test]
+    fn test_set_span() {
+        let at = | secs: i64 | DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let timer = || {
+            let mut t = Timer::from("test".to_string());
+            t.add_session(at(0), at(100)).unwrap();
+            t.add_session(at(200), at(300)).unwrap();
+            t.add_session(at(400), at(86400)).unwrap();
+            t
+        };
+
+        let mut t = timer();
+        t.set_span(None, Some(at(500)), None).expect("err");
+        assert_eq!(t.time_on(), 300);
+        assert_eq!((t.end, t.idle), (at(500), 200));
+
+        let mut t = timer();
+        t.set_span(None, Some(at(500)), Some(350)).expect("err");
+        assert_eq!(t.time_on(), 150);
+        assert_eq!(t.sessions.len(), 2);
+        assert_eq!(t.sessions[1].end, Some(at(250)));
+
+        let mut t = timer();
+        t.set_span(Some(at(-100)), Some(at(500)), Some(0)).expect("err");
+        assert_eq!(t.time_on(), 600);
+        assert_eq!(t.sessions[0].start, at(-100));
+
+        let mut t = timer();
+        t.set_span(Some(at(250)), Some(at(500)), None).expect("err");
+        assert_eq!(t.sessions.len(), 2);
+        assert_eq!(t.time_on(), 150);
+
+        let mut t = timer();
+        assert_eq!(t.set_span(Some(at(600)), Some(at(500)), None), Err(TimerError::WrongInterval));
+        assert_eq!(t.set_span(None, Some(at(500)), Some(501)), Err(TimerError::WrongIdle));
+        assert_eq!(t.set_span(None, Some(at(500)), Some(-1)), Err(TimerError::WrongIdle));
+        t.set_start().unwrap();
+        assert_eq!(t.set_span(None, Some(at(500)), None), Err(TimerError::TimerIsRunning));
+    }
+
+    #[test]
+    fn test_delete_restore() {
+        let mut t = Timer::from("test".to_string());
+        assert_eq!(t.set_restore(), Err(TimerError::TimerIsNotDeleted));
+        t.set_start().unwrap();
+        t.set_delete().unwrap();
+        assert_eq!(t.status, TimerStatus::DELETED);
+        assert!(t.deleted_at.is_some());
+        assert!(t.sessions.iter().all(| s | s.end.is_some()));
+        t.set_restore().unwrap();
+        assert_eq!(t.status, TimerStatus::PAUSED);
+        assert!(t.deleted_at.is_none());
+
+        t.set_delete().unwrap();
+        assert_eq!(t.set_complete(), Err(TimerError::TimerHasFiniteState));
+        assert_eq!(t.status, TimerStatus::DELETED);
+        assert!(t.deleted_at.is_some());
+        t.set_restore().unwrap();
+
+        t.set_complete().unwrap();
+        t.set_delete().unwrap();
+        t.set_delete().unwrap();
+        t.set_restore().unwrap();
+        assert_eq!(t.status, TimerStatus::COMPLETED);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let mut t = Timer::from("test".to_string());
+        assert_eq!(t.set_reopen(), Err(TimerError::TimerIsNotCompleted));
+        t.set_complete().unwrap();
+        let completed_at = t.completed_at;
+        assert!(completed_at.is_some());
+        let time_on = t.time_on();
+
+        t.set_reopen().unwrap();
+        assert_eq!(t.status, TimerStatus::PAUSED);
+        assert!(t.reopened_at.is_some());
+        assert_eq!(t.time_on(), time_on);
+        t.set_start().unwrap();
+        t.set_complete().unwrap();
+        assert_eq!(t.completed_at, completed_at);
+        assert_eq!(t.sessions.len(), 2);
+    }
+
+    #[test]
+    fn test_time_by_tag() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let t1 = DateTime::from_timestamp(1_700_000_060, 0).unwrap();
+        let timer = | tags: &[&str] | {
+            let mut t = Timer::new(1, "t".to_string(), t0, t1, 0, TimerStatus::PAUSED);
+            t.sessions = t.legacy_sessions();
+            t.tags = tags.iter().map(| t | t.to_string()).collect();
+            t
+        };
+        let collection = TimerCollection::from(vec![
+            timer(&["coding", "review"]),
+            timer(&["coding"]),
+            timer(&[])
+        ]);
+        let tags = collection.time_by_tag();
+        assert_eq!(tags.get("coding"), Some(&(2, 120)));
+        assert_eq!(tags.get("review"), Some(&(1, 60)));
+        assert_eq!(tags.get(""), Some(&(1, 60)));
+    }
+
+    #[test]
+    fn test_legacy_row_time() {
+        let row = SQLTimerRow {
+            id: 1,
+            task: "test".to_string(),
+            start: 1_700_000_000,
+            end: 1_700_000_600,
+            idle: 100,
+            status: TimerStatus::PAUSED as u32,
+            ..SQLTimerRow::default()
+        };
+        let t = Timer::try_from(row).unwrap();
+        assert_eq!(t.sessions.len(), 1);
+        assert_eq!(t.time_on(), 500);
+    }
 }
@@ -0,0 +1,219 @@
+use std::error::Error;
+use std::str::FromStr;
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc, Weekday};
+use csv::Writer;
+use crate::timer::{Period, TimerCollection};
+
+
+/// What the rows of a timesheet are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SheetRows {
+    Task,
+    Project
+}
+
+impl FromStr for SheetRows {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "task" => Ok(SheetRows::Task),
+            "project" => Ok(SheetRows::Project),
+            _ => Err(format!("Unknown rows {s}, use task or project"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SheetFormat {
+    Text,
+    Csv,
+    Markdown
+}
+
+impl FromStr for SheetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SheetFormat::Text),
+            "csv" => Ok(SheetFormat::Csv),
+            "markdown" | "md" => Ok(SheetFormat::Markdown),
+            _ => Err(format!("Unknown format {s}, use text, csv or markdown"))
+        }
+    }
+}
+
+/// Start of an ISO week given like `2025-W03`.
+pub fn parse_week(week: &str) -> Option<DateTime<Utc>> {
+    let (year, n) = week.trim().to_uppercase().split_once("-W")
+        .map(| (y, n) | (y.parse::<i32>(), n.parse::<u32>()))?;
+    let day = NaiveDate::from_isoywd_opt(year.ok()?, n.ok()?, Weekday::Mon)?;
+    Some(day.and_time(NaiveTime::MIN).and_utc())
+}
+
+fn hours(seconds: i64) -> String {
+    format!("{:.2}", seconds as f64 / 3600.0)
+}
+
+/// Seconds spent per row and weekday of one week.
+#[derive(Debug)]
+pub struct Timesheet {
+    pub start: DateTime<Utc>,
+    pub by: SheetRows,
+    pub rows: Vec<(String, [i64; 7])>
+}
+
+impl Timesheet {
+
+    /// Splits the sessions of the week into days, rows are ordered by name.
+    pub fn new(collection: &TimerCollection, start: DateTime<Utc>, by: SheetRows) -> Self {
+        let end = start + Days::new(7);
+        let mut rows: Vec<(String, [i64; 7])> = vec![];
+        for (day, tasks) in collection.report(Period::Day, Some(start), Some(end)) {
+            let n = (day - start).num_days() as usize;
+            for (t, time_on) in tasks {
+                let name = match by {
+                    SheetRows::Task => format!("{} ({})", t.task, t.id),
+                    SheetRows::Project => t.project.clone().unwrap_or_else(|| String::from("(no project)"))
+                };
+                match rows.iter_mut().find(| (row, _) | *row == name) {
+                    Some((_, days)) => days[n] += time_on,
+                    None => {
+                        let mut days = [0; 7];
+                        days[n] = time_on;
+                        rows.push((name, days));
+                    }
+                }
+            }
+        }
+        rows.sort_by(| a, b | a.0.cmp(&b.0));
+        Timesheet { start, by, rows }
+    }
+
+    /// Header, one line per row and the totals, time is in hours.
+    fn table(&self, empty: &str) -> Vec<Vec<String>> {
+        let cell = | seconds: i64 | if seconds == 0 { empty.to_string() } else { hours(seconds) };
+        let mut header = vec![String::from(match self.by {
+            SheetRows::Task => "Task",
+            SheetRows::Project => "Project"
+        })];
+        for n in 0..7 {
+            header.push((self.start + Days::new(n)).format("%a %m-%d").to_string());
+        }
+        header.push(String::from("Total"));
+        let mut table = vec![header];
+        let mut totals = [0; 7];
+        for (name, days) in self.rows.iter() {
+            let mut line = vec![name.clone()];
+            line.extend(days.iter().map(| s | cell(*s)));
+            line.push(cell(days.iter().sum()));
+            table.push(line);
+            for (total, s) in totals.iter_mut().zip(days) {
+                *total += s;
+            }
+        }
+        let mut line = vec![String::from("Total")];
+        line.extend(totals.iter().map(| s | cell(*s)));
+        line.push(cell(totals.iter().sum()));
+        table.push(line);
+        table
+    }
+
+    pub fn render(&self, format: SheetFormat) -> Result<String, Box<dyn Error>> {
+        let title = self.start.format("%G-W%V").to_string();
+        let out = match format {
+            SheetFormat::Text => {
+                let table = self.table("-");
+                let widths: Vec<usize> = (0..table[0].len())
+                    .map(| i | table.iter().map(| l | l[i].chars().count()).max().unwrap_or(0))
+                    .collect();
+                let mut out = format!("==>> Timesheet {title} <<==\n");
+                for line in table {
+                    let cells: Vec<String> = line.iter().zip(widths.iter()).enumerate()
+                        .map(| (i, (c, w)) | if i == 0 { format!("{c:<w$}") } else { format!("{c:>w$}") })
+                        .collect();
+                    out.push_str(cells.join("  ").trim_end());
+                    out.push('\n');
+                }
+                out
+            },
+            SheetFormat::Csv => {
+                let mut wrt = Writer::from_writer(vec![]);
+                for line in self.table("0.00") {
+                    wrt.write_record(&line)?;
+                }
+                String::from_utf8(wrt.into_inner()?)?
+            },
+            SheetFormat::Markdown => {
+                let table = self.table("");
+                let line = | cells: &Vec<String> | format!(
+                    "| {} |\n",
+                    cells.iter().map(| c | c.replace('|', "\\|")).collect::<Vec<_>>().join(" | ")
+                );
+                let mut out = format!("### Timesheet {title}\n\n");
+                out.push_str(&line(&table[0]));
+                out.push_str(&format!("|---{}|\n", "|--:".repeat(table[0].len() - 1)));
+                for cells in table[1..].iter() {
+                    out.push_str(&line(cells));
+                }
+                out
+            }
+        };
+        Ok(out)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::{Session, Timer};
+
+    #[test]
+    fn test_parse_week() {
+        let monday = NaiveDate::from_ymd_opt(2025, 1, 13).unwrap().and_time(NaiveTime::MIN).and_utc();
+        assert_eq!(parse_week("2025-W03"), Some(monday));
+        assert_eq!(parse_week("2025-w3"), Some(monday));
+        assert_eq!(parse_week("2025-W54"), None);
+        assert_eq!(parse_week("2025-03"), None);
+    }
+
+    #[test]
+    fn test_timesheet() {
+        let start = parse_week("2025-W03").unwrap();
+        let at = | day: u64, hour: i64 | start + Days::new(day) + chrono::Duration::hours(hour);
+        let timer = | id, task: &str, project: Option<&str>, sessions: Vec<Session> | {
+            let mut t = Timer::from(task.to_string());
+            t.id = id;
+            t.project = project.map(String::from);
+            t.sessions = sessions;
+            t
+        };
+        let collection = TimerCollection::from(vec![
+            timer(1, "deploy", Some("work"), vec![
+                Session::new(at(0, 9), Some(at(0, 11))),
+                Session::new(at(6, 23), Some(at(7, 1)))
+            ]),
+            timer(2, "read | notes", None, vec![Session::new(at(2, 10), Some(at(2, 10) + chrono::Duration::minutes(30)))]),
+            timer(3, "review", Some("work"), vec![Session::new(at(2, 12), Some(at(2, 13)))])
+        ]);
+
+        let sheet = Timesheet::new(&collection, start, SheetRows::Task);
+        assert_eq!(sheet.rows[0], (String::from("deploy (1)"), [7200, 0, 0, 0, 0, 0, 3600]));
+        let csv = sheet.render(SheetFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Task,Mon 01-13,Tue 01-14,Wed 01-15,Thu 01-16,Fri 01-17,Sat 01-18,Sun 01-19,Total");
+        assert_eq!(lines[4], "Total,2.00,0.00,1.50,0.00,0.00,0.00,1.00,4.50");
+
+        let sheet = Timesheet::new(&collection, start, SheetRows::Project);
+        assert_eq!(sheet.rows, vec![
+            (String::from("(no project)"), [0, 0, 1800, 0, 0, 0, 0]),
+            (String::from("work"), [7200, 0, 3600, 0, 0, 0, 3600])
+        ]);
+        let markdown = Timesheet::new(&collection, start, SheetRows::Task).render(SheetFormat::Markdown).unwrap();
+        assert!(markdown.contains("| read \\| notes (2) |  |  | 0.50 |"));
+        let text = sheet.render(SheetFormat::Text).unwrap();
+        assert!(text.lines().last().unwrap().ends_with("4.50"));
+    }
+}